@@ -0,0 +1,178 @@
+//! The aliases of `sendemail.aliasesfile`, just like git send-email, so that `--cc ml-dri` can
+//! stand for the address of the list.
+//!
+//! `sendemail.aliasfiletype` tells the format of the files, `mutt` or `mailrc`. An alias can
+//! stand for several addresses, and for other aliases.
+
+use std::{collections::HashMap, str::FromStr};
+
+use gix_config::{path::interpolate::Context as PathContext, File as GitFile};
+use lettre::message::Mailbox;
+
+use crate::{get_config_string, Error};
+
+/// How deep aliases standing for other aliases can go, so that loops end.
+const MAX_DEPTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Mutt,
+    Mailrc,
+}
+
+/// Splits `s` at each character of `separators` that isn't quoted or between angle brackets.
+fn split_unquoted<'a>(s: &'a str, separators: &[char]) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let (mut quoted, mut bracketed) = (false, false);
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '<' if !quoted => bracketed = true,
+            '>' if !quoted => bracketed = false,
+            c if separators.contains(&c) && !quoted && !bracketed => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Returns `address` without the quotes around it, as mailrc needs for the ones with spaces.
+fn unquote(address: &str) -> &str {
+    address
+        .strip_prefix('"')
+        .and_then(|a| a.strip_suffix('"'))
+        .filter(|a| !a.contains('"'))
+        .unwrap_or(address)
+}
+
+/// Parses an `alias NAME ADDRESS...` line of `format`, into the name and the addresses.
+fn parse_line(line: &str, format: Format) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    let rest = line
+        .strip_prefix("alias")
+        .or_else(|| line.strip_prefix("group"))
+        .filter(|r| r.starts_with([' ', '\t']))?
+        .trim_start();
+
+    // mutt puts the groups of the alias before its name.
+    let mut rest = rest;
+    while format == Format::Mutt && rest.starts_with("-group") {
+        rest = rest
+            .split_once(char::is_whitespace)
+            .and_then(|(_, r)| r.trim_start().split_once(char::is_whitespace))
+            .map_or("", |(_, r)| r.trim_start());
+    }
+
+    let (name, addresses) = rest.split_once(char::is_whitespace)?;
+    let addresses = match format {
+        Format::Mutt => split_unquoted(addresses, &[',']),
+        Format::Mailrc => split_unquoted(addresses, &[' ', '\t', ',']),
+    };
+
+    Some((
+        name.to_string(),
+        addresses
+            .into_iter()
+            .map(|a| unquote(a).to_string())
+            .collect(),
+    ))
+}
+
+/// The aliases of all the alias files.
+#[derive(Debug, Default)]
+pub(crate) struct Aliases(HashMap<String, Vec<String>>);
+
+impl Aliases {
+    /// Reads the alias files of `sendemail.aliasesfile`, if any.
+    pub(crate) fn load(cfg: &GitFile<'_>) -> Result<Self, Error> {
+        let Some(paths) = cfg.strings_by_key("sendemail.aliasesfile") else {
+            return Ok(Aliases::default());
+        };
+
+        let format = match get_config_string(cfg, "sendemail.aliasfiletype").as_deref() {
+            Some("mutt") => Format::Mutt,
+            Some("mailrc") => Format::Mailrc,
+            Some(t) => {
+                return Err(Error::InvalidConfig(
+                    "sendemail.aliasfiletype",
+                    format!("{t}, only mutt and mailrc are supported"),
+                ))
+            }
+            None => return Err(Error::MissingConfig("sendemail.aliasfiletype")),
+        };
+
+        let mut aliases = HashMap::new();
+        for path in paths {
+            let path = gix_config::Path::from(path)
+                .interpolate(PathContext {
+                    home_dir: home::home_dir().as_deref(),
+                    ..Default::default()
+                })
+                .map_err(|e| Error::InvalidConfig("sendemail.aliasesfile", e.to_string()))?
+                .into_owned();
+
+            let content = std::fs::read_to_string(&path).map_err(|e| Error::Io(path, e))?;
+
+            // A backslash at the end of a line continues it on the next one.
+            let content = content.replace("\\\n", " ");
+            aliases.extend(
+                content
+                    .lines()
+                    .filter(|l| !l.trim_start().starts_with('#'))
+                    .filter_map(|l| parse_line(l, format)),
+            );
+        }
+
+        Ok(Aliases(aliases))
+    }
+
+    fn expand_into(
+        &self,
+        field: &'static str,
+        value: &str,
+        depth: usize,
+        mailboxes: &mut Vec<Mailbox>,
+    ) -> Result<(), Error> {
+        for address in split_unquoted(value, &[',']) {
+            match self.0.get(address) {
+                Some(expansion) if depth < MAX_DEPTH => {
+                    for a in expansion {
+                        self.expand_into(field, a, depth + 1, mailboxes)?;
+                    }
+                }
+                Some(_) => {
+                    return Err(Error::InvalidConfig(
+                        "sendemail.aliasesfile",
+                        format!("{address} is an alias of itself"),
+                    ))
+                }
+                None => mailboxes.push(
+                    Mailbox::from_str(address)
+                        .map_err(|_| Error::InvalidAddress(field, address.to_string()))?,
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the addresses of `value`, a comma-separated list of addresses and aliases given
+    /// as `field`.
+    pub(crate) fn expand(&self, field: &'static str, value: &str) -> Result<Vec<Mailbox>, Error> {
+        let mut mailboxes = Vec::new();
+        self.expand_into(field, value, 0, &mut mailboxes)?;
+
+        Ok(mailboxes)
+    }
+}
@@ -0,0 +1,57 @@
+//! Maintainer replies, telling the author their patch was applied to one of our branches.
+
+use crate::{git, Error};
+
+/// Returns the title of the commit a patch becomes, without the `[PATCH v2 1/3]` like prefix.
+pub(crate) fn get_title(subject: &str) -> &str {
+    let mut title = subject.trim();
+
+    while let Some(rest) = title.strip_prefix('[') {
+        let Some((_, rest)) = rest.split_once(']') else {
+            break;
+        };
+
+        title = rest.trim_start();
+    }
+
+    title
+}
+
+/// Returns the commit of `branch` whose title is the one of the patch sent as `subject`.
+fn find_commit(branch: &str, subject: &str) -> Result<Option<String>, Error> {
+    let title = get_title(subject);
+    let log = git::run(&[
+        "log",
+        "--format=%H %s",
+        "--max-count",
+        git::MAX_COMMITS,
+        branch,
+        "--",
+    ])?;
+
+    Ok(log
+        .lines()
+        .filter_map(|l| l.split_once(' '))
+        .find(|(_, s)| *s == title)
+        .map(|(hash, _)| hash.to_string()))
+}
+
+/// Returns the text telling the patch sent as `subject` was applied to `branch`, or to the
+/// current branch if it's empty.
+pub(crate) fn get_text(branch: &str, subject: &str) -> Result<String, Error> {
+    let branch = if branch.is_empty() {
+        git::run(&["rev-parse", "--abbrev-ref", "HEAD"])?
+            .trim()
+            .to_string()
+    } else {
+        branch.to_string()
+    };
+
+    Ok(match find_commit(&branch, subject)? {
+        Some(hash) => format!(
+            "Applied to {branch} as commit {}, thanks!",
+            hash.get(..12).unwrap_or(&hash)
+        ),
+        None => format!("Applied to {branch}, thanks!"),
+    })
+}
@@ -0,0 +1,161 @@
+//! Patches attached to the message rather than sent inline: as an embedded `message/rfc822`
+//! message, as some MUAs forward them, or as a `text/x-patch` or `text/x-diff` file, usually
+//! the output of git format-patch.
+//!
+//! The reply is built against the message as if the patch had been inline: it keeps the
+//! headers of the message we reply to, but gets the body and the subject of the patch, with
+//! its author in an in-body `From:` line if someone else sent it.
+
+use std::fmt::Write as _;
+
+use mail_parser::{Message, MessageParser, MimeHeaders, PartType};
+
+use crate::{format_address, patch};
+
+/// The subtypes of the `text` attachments that carry a patch.
+const PATCH_TYPES: [&str; 2] = ["x-patch", "x-diff"];
+
+/// The extensions of the attachments that carry a patch, whatever their type.
+const PATCH_EXTENSIONS: [&str; 2] = [".patch", ".diff"];
+
+/// The headers of the message that describe its body, which the patch replaces.
+const BODY_HEADERS: [&str; 4] = [
+    "MIME-Version",
+    "Content-Type",
+    "Content-Transfer-Encoding",
+    "Content-Disposition",
+];
+
+/// A patch found in an attachment.
+struct Patch {
+    author: Option<String>,
+    subject: Option<String>,
+    body: String,
+}
+
+impl Patch {
+    /// Returns the patch of `msg`, an embedded message or the output of git format-patch, if
+    /// it has a diff.
+    fn from_message(msg: &Message<'_>) -> Option<Self> {
+        let body = msg.body_text(0)?;
+        if !patch::has_diff(&body) {
+            return None;
+        }
+
+        Some(Patch {
+            author: msg.from().map(format_address),
+            subject: msg.subject().map(String::from),
+            body: body.into_owned(),
+        })
+    }
+
+    /// Returns the patch in `text`, with its headers if git format-patch wrote them.
+    fn from_text(text: &str) -> Option<Self> {
+        // The first line of git format-patch comes from mboxes, and isn't a header.
+        let headers = match text.split_once('\n') {
+            Some((first, rest)) if first.starts_with("From ") => rest,
+            _ => text,
+        };
+
+        if let Some(patch) = MessageParser::default()
+            .parse(headers.as_bytes())
+            .filter(|m| m.subject().is_some())
+            .and_then(|m| Patch::from_message(&m))
+        {
+            return Some(patch);
+        }
+
+        patch::has_diff(text).then(|| Patch {
+            author: None,
+            subject: None,
+            body: text.to_string(),
+        })
+    }
+}
+
+/// Returns whether the attachment `part` is a file carrying a patch.
+fn is_patch_file(part: &mail_parser::MessagePart<'_>) -> bool {
+    let patch_type = part.content_type().is_some_and(|ct| {
+        ct.ctype().eq_ignore_ascii_case("text")
+            && ct
+                .subtype()
+                .is_some_and(|s| PATCH_TYPES.iter().any(|t| s.eq_ignore_ascii_case(t)))
+    });
+    let patch_name = part.attachment_name().is_some_and(|n| {
+        let n = n.to_ascii_lowercase();
+        PATCH_EXTENSIONS.iter().any(|e| n.ends_with(e))
+    });
+
+    patch_type || patch_name
+}
+
+/// Returns the first patch attached to `msg`.
+fn find_patch(msg: &Message<'_>) -> Option<Patch> {
+    msg.attachments().find_map(|part| match &part.body {
+        PartType::Message(embedded) => Patch::from_message(embedded),
+        PartType::Text(text) if is_patch_file(part) => Patch::from_text(text),
+        PartType::Binary(data) if is_patch_file(part) => {
+            Patch::from_text(&String::from_utf8_lossy(data))
+        }
+        _ => None,
+    })
+}
+
+/// Returns whether the header `line` is one of `names`.
+fn is_header(line: &str, names: &[&str]) -> bool {
+    line.split_once(':')
+        .is_some_and(|(name, _)| names.iter().any(|n| name.trim().eq_ignore_ascii_case(n)))
+}
+
+/// Returns `buffer`, the raw message we reply to, with the patch attached to it inline, or as
+/// it is if the patch is already inline or there's none.
+pub(crate) fn inline(buffer: Vec<u8>) -> Vec<u8> {
+    let Some(msg) = MessageParser::default().parse(&buffer) else {
+        return buffer;
+    };
+
+    if msg.body_text(0).is_some_and(|b| patch::has_diff(&b)) {
+        return buffer;
+    }
+
+    let Some(patch) = find_patch(&msg) else {
+        return buffer;
+    };
+
+    let root = msg.root_part();
+    let headers =
+        String::from_utf8_lossy(&buffer[root.raw_header_offset()..root.raw_body_offset()]);
+
+    let mut dropped = BODY_HEADERS.to_vec();
+    if patch.subject.is_some() {
+        dropped.push("Subject");
+    }
+
+    // A header goes on over the lines starting with whitespace.
+    let mut inlined = String::new();
+    let mut dropping = false;
+    for line in headers.split_inclusive('\n') {
+        if !line.starts_with([' ', '\t']) {
+            dropping = is_header(line, &dropped);
+        }
+
+        if !dropping && !line.trim().is_empty() {
+            inlined.push_str(line);
+        }
+    }
+
+    if let Some(subject) = &patch.subject {
+        let _ = writeln!(inlined, "Subject: {subject}");
+    }
+    inlined.push_str("MIME-Version: 1.0\n");
+    inlined.push_str("Content-Type: text/plain; charset=utf-8\n");
+    inlined.push_str("Content-Transfer-Encoding: 8bit\n\n");
+
+    let sender = msg.from().map(format_address);
+    if let Some(author) = patch.author.filter(|a| Some(a) != sender.as_ref()) {
+        let _ = writeln!(inlined, "From: {author}\n");
+    }
+    inlined.push_str(&patch.body);
+
+    inlined.into_bytes()
+}
@@ -0,0 +1,96 @@
+//! Sending of several messages in a row, the replies to a series or the queued ones. Over SMTP,
+//! they go through lettre's async transport, `acker.sendConcurrency` at a time over its pool of
+//! connections, so that a slow link doesn't add up its latency for each of them. Sendmail and
+//! the files get them one by one.
+
+use std::{io::Write, time::Duration};
+
+use futures_util::{stream, StreamExt};
+use gix_config::File as GitFile;
+use lettre::{address::Envelope, Transport};
+
+use crate::{get_config_usize, Error, MailTransport, SmtpRelay};
+
+/// How many messages we send at once over SMTP, unless `acker.sendConcurrency` says otherwise.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// How many times we try to send a message before giving up on it.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Returns how many messages to send at once through `transport`.
+pub(crate) fn get_concurrency(
+    cfg: &GitFile<'_>,
+    transport: &MailTransport,
+) -> Result<usize, Error> {
+    if !matches!(transport, MailTransport::Smtp(_)) {
+        return Ok(1);
+    }
+
+    Ok(get_config_usize(cfg, "acker.sendConcurrency")?
+        .unwrap_or(DEFAULT_CONCURRENCY)
+        .max(1))
+}
+
+/// Sends a message over SMTP, trying again a few times, a bit later each time, if the error
+/// might go away, like a network that's still down.
+async fn send_smtp(relay: &SmtpRelay, envelope: &Envelope, email: &[u8]) -> Result<(), String> {
+    let mut attempt = 1;
+
+    loop {
+        let Err(e) = relay.send_raw(envelope, email).await else {
+            return Ok(());
+        };
+
+        // Only SMTP tells apart the errors that might go away.
+        let retry = !(e.is_permanent() || e.is_client() || e.is_tls());
+        if !retry || attempt >= MAX_ATTEMPTS {
+            return Err(e.to_string());
+        }
+
+        let delay = 2_u64.pow(attempt);
+        eprintln!("acker: warning: {e}, trying again in {delay}s");
+        tokio::time::sleep(Duration::from_secs(delay)).await;
+        attempt += 1;
+    }
+}
+
+/// Sends a message on its own.
+fn send(transport: &MailTransport, envelope: &Envelope, email: &[u8]) -> Result<(), String> {
+    match transport {
+        MailTransport::Smtp(relay) => relay.block_on(send_smtp(relay, envelope, email)),
+        MailTransport::Sendmail(t) => t.send_raw(envelope, email).map_err(|e| e.to_string()),
+        MailTransport::File(t) => t
+            .send_raw(envelope, email)
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        MailTransport::Stdout => std::io::stdout()
+            .write_all(email)
+            .map_err(|e| e.to_string()),
+        MailTransport::Capture(c) => {
+            c.push(envelope, email);
+            Ok(())
+        }
+    }
+}
+
+/// Sends all the `emails`, at most `concurrency` at once over SMTP and one by one through the
+/// other transports, and returns how it went for each of them, in the same order.
+pub(crate) fn send_all(
+    transport: &MailTransport,
+    emails: &[(Envelope, Vec<u8>)],
+    concurrency: usize,
+) -> Vec<Result<(), String>> {
+    if let MailTransport::Smtp(relay) = transport {
+        return relay.block_on(
+            stream::iter(emails)
+                .map(|(envelope, email)| send_smtp(relay, envelope, email))
+                .buffered(concurrency.max(1))
+                .collect(),
+        );
+    }
+
+    emails
+        .iter()
+        .map(|(envelope, email)| send(transport, envelope, email))
+        .collect()
+}
@@ -0,0 +1,140 @@
+//! SMTP and IMAP secrets that don't belong in the git configuration: the passwords of the git
+//! credential helpers, through `git credential` just like git send-email does when
+//! `sendemail.smtppass` isn't set, and the OAuth tokens of `acker.oauthTokenCmd`.
+
+use std::{
+    fmt::{self, Write as _},
+    io::Write as _,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use crate::{git::GIT, Error};
+
+/// A credential as the git credential protocol describes it, a set of `key=value` attributes.
+pub(crate) struct Credential {
+    attributes: Vec<(String, String)>,
+}
+
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credential").finish_non_exhaustive()
+    }
+}
+
+fn format_attributes(attributes: &[(String, String)]) -> String {
+    let mut input = String::new();
+    for (key, value) in attributes {
+        let _ = writeln!(input, "{key}={value}");
+    }
+    input.push('\n');
+    input
+}
+
+/// Runs `git credential <action>` with `attributes`, and returns what it printed.
+fn run(action: &str, attributes: &[(String, String)]) -> Result<String, Error> {
+    let io_err = |e| Error::Io(PathBuf::from(GIT), e);
+
+    // The helpers might need to ask the user, so stderr and the terminal are theirs.
+    let mut child = Command::new(GIT)
+        .args(["credential", action])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(io_err)?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(format_attributes(attributes).as_bytes())
+            .map_err(io_err)?;
+    }
+
+    let output = child.wait_with_output().map_err(io_err)?;
+    if !output.status.success() {
+        return Err(Error::Git(format!("git credential {action} failed")));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl Credential {
+    /// Asks the credential helpers for the password of `user` on the `protocol` server `host`,
+    /// with the port if it isn't the default one. Returns `None` if they have none.
+    pub(crate) fn fill(protocol: &str, host: &str, user: &str) -> Result<Option<Self>, Error> {
+        let query = vec![
+            (String::from("protocol"), protocol.to_string()),
+            (String::from("host"), host.to_string()),
+            (String::from("username"), user.to_string()),
+        ];
+
+        let attributes: Vec<_> = run("fill", &query)?
+            .lines()
+            .filter_map(|l| l.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        if !attributes.iter().any(|(key, _)| key == "password") {
+            return Ok(None);
+        }
+
+        Ok(Some(Credential { attributes }))
+    }
+
+    pub(crate) fn username(&self) -> &str {
+        self.get("username").unwrap_or_default()
+    }
+
+    pub(crate) fn password(&self) -> &str {
+        self.get("password").unwrap_or_default()
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Tells the helpers the credential worked, so that they can store it.
+    pub(crate) fn approve(&self) {
+        if let Err(e) = run("approve", &self.attributes) {
+            eprintln!("acker: warning: {e}");
+        }
+    }
+
+    /// Tells the helpers the credential was refused, so that they can forget it.
+    pub(crate) fn reject(&self) {
+        if let Err(e) = run("reject", &self.attributes) {
+            eprintln!("acker: warning: {e}");
+        }
+    }
+}
+
+/// Runs the `acker.oauthTokenCmd` command, and returns the OAuth access token it printed.
+pub(crate) fn oauth_token(command: &str) -> Result<String, Error> {
+    // The tools might need to open a browser or ask the user, stdout is the only thing we take.
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| Error::Io(PathBuf::from("sh"), e))?;
+
+    if !output.status.success() {
+        return Err(Error::OauthToken(
+            command.to_string(),
+            output.status.to_string(),
+        ));
+    }
+
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if token.is_empty() {
+        return Err(Error::OauthToken(
+            command.to_string(),
+            String::from("it printed no token"),
+        ));
+    }
+
+    Ok(token)
+}
@@ -0,0 +1,240 @@
+//! Defaults of the reply options from the git configuration, such as `acker.dryRun` or
+//! `acker.autoCc`, for the flags people give on every run.
+//!
+//! The command line has the last word: each of these flags gets a `--no-` counterpart, or loses
+//! it for the ones that already start with `--no-`, to turn off what the configuration turns on.
+
+use clap::{builder::ArgAction, parser::ValueSource, Arg, ArgMatches, CommandFactory};
+use gix_config::File as GitFile;
+
+use crate::{
+    get_config_bool, get_config_string, get_config_usize, parse_charset, parse_reply_count, Args,
+    Error,
+};
+
+/// Returns the flag of `args` with the id of the same name.
+type Field = fn(&mut Args) -> &mut bool;
+
+/// The flags whose default is in the configuration: their id, their key, and whether the key
+/// is the opposite of the flag, as `acker.signature` is of `--no-signature`.
+const FLAGS: [(&str, &str, bool, Field); 28] = [
+    ("thread", "acker.thread", false, |a| &mut a.thread),
+    ("dry", "acker.dryRun", false, |a| &mut a.dry),
+    ("strict", "acker.strict", false, |a| &mut a.strict),
+    ("exit_code", "acker.exitCode", false, |a| &mut a.exit_code),
+    ("check_apply", "acker.checkApply", false, |a| {
+        &mut a.check_apply
+    }),
+    ("record_test_cmd", "acker.recordTestCmd", false, |a| {
+        &mut a.record_test_cmd
+    }),
+    ("quote_headers", "acker.quoteHeaders", false, |a| {
+        &mut a.quote_headers
+    }),
+    ("sign", "acker.sign", false, |a| &mut a.sign),
+    ("smime", "acker.smime", false, |a| &mut a.smime),
+    ("no_signature", "acker.signature", true, |a| {
+        &mut a.no_signature
+    }),
+    ("no_cc_self", "acker.ccSelf", true, |a| &mut a.no_cc_self),
+    ("respect_headers", "acker.respectHeaders", false, |a| {
+        &mut a.respect_headers
+    }),
+    ("auto_cc", "acker.autoCc", false, |a| &mut a.auto_cc),
+    ("cc_trailers", "acker.ccTrailers", false, |a| {
+        &mut a.cc_trailers
+    }),
+    ("ignore_reply_to", "acker.ignoreReplyTo", false, |a| {
+        &mut a.ignore_reply_to
+    }),
+    ("no_references", "acker.references", true, |a| {
+        &mut a.no_references
+    }),
+    ("report_trailers", "acker.reportTrailers", false, |a| {
+        &mut a.report_trailers
+    }),
+    ("confirm", "acker.confirm", false, |a| &mut a.confirm),
+    ("edit", "acker.edit", false, |a| &mut a.edit),
+    (
+        "confirm_recipients",
+        "acker.confirmRecipients",
+        false,
+        |a| &mut a.confirm_recipients,
+    ),
+    ("link", "acker.link", false, |a| &mut a.link),
+    ("attribution_only", "acker.attributionOnly", false, |a| {
+        &mut a.attribution_only
+    }),
+    ("quote_all", "acker.quoteAll", false, |a| &mut a.quote_all),
+    ("with_diffstat", "acker.withDiffstat", false, |a| {
+        &mut a.with_diffstat
+    }),
+    ("echo_trailers", "acker.echoTrailers", false, |a| {
+        &mut a.echo_trailers
+    }),
+    ("use_patch_author", "acker.usePatchAuthor", false, |a| {
+        &mut a.use_patch_author
+    }),
+    ("skip_if_tagged", "acker.skipIfTagged", false, |a| {
+        &mut a.skip_if_tagged
+    }),
+    ("author_only", "acker.authorOnly", false, |a| {
+        &mut a.author_only
+    }),
+];
+
+/// Returns whether `key` is the key of one of [`FLAGS`].
+pub(crate) fn is_flag_key(key: &str) -> bool {
+    FLAGS.iter().any(|(_, k, _, _)| k.eq_ignore_ascii_case(key))
+}
+
+/// Returns the id of the flag undoing `id`.
+fn negation_id(id: &str) -> String {
+    format!("negate_{id}")
+}
+
+/// Adds the flags undoing the ones of [`FLAGS`] to `cmd`, the command taking the reply options.
+pub(crate) fn add_negations(mut cmd: clap::Command) -> clap::Command {
+    for (id, _, _, _) in FLAGS {
+        let Some(long) = cmd
+            .get_arguments()
+            .find(|a| a.get_id() == id)
+            .and_then(Arg::get_long)
+        else {
+            continue;
+        };
+
+        let negation = match long.strip_prefix("no-") {
+            Some(positive) => positive.to_string(),
+            None => format!("no-{long}"),
+        };
+
+        let negation_id = negation_id(id);
+        cmd = cmd
+            .mut_arg(id, |a| a.overrides_with(negation_id.clone()))
+            .arg(
+                Arg::new(negation_id.clone())
+                    .long(negation)
+                    .action(ArgAction::SetTrue)
+                    .overrides_with(id)
+                    .hide(true),
+            );
+    }
+
+    cmd
+}
+
+impl Args {
+    /// Remembers the flags the command line decides on, so that the configuration doesn't turn
+    /// them back on: the ones turned off, and the ones that conflict with the options given.
+    pub(crate) fn record_negations(&mut self, matches: &ArgMatches) {
+        let mut cmd = add_negations(Args::command());
+        cmd.build();
+
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        for (id, _, _, _) in FLAGS {
+            let Some(arg) = cmd.get_arguments().find(|a| a.get_id() == id) else {
+                continue;
+            };
+
+            // Conflicts are declared on either side.
+            let conflicting = cmd.get_arguments().any(|other| {
+                given(other.get_id().as_str())
+                    && (cmd.get_arg_conflicts_with(arg).contains(&other)
+                        || cmd.get_arg_conflicts_with(other).contains(&arg))
+            });
+            if given(&negation_id(id)) || conflicting {
+                self.negated.push(id);
+            }
+        }
+    }
+
+    /// Makes sure the configuration didn't turn on flags that the command line refuses together,
+    /// such as `acker.attributionOnly` and `acker.quoteAll`.
+    fn check_conflicts(&mut self) -> Result<(), Error> {
+        let mut cmd = Args::command();
+        cmd.build();
+
+        let arg = |id: &str| cmd.get_arguments().find(|a| a.get_id() == id);
+        for (i, (id, key, inverted, field)) in FLAGS.iter().enumerate() {
+            if !*field(self) {
+                continue;
+            }
+
+            for (other_id, other_key, other_inverted, other_field) in &FLAGS[..i] {
+                let (Some(a), Some(b)) = (arg(id), arg(other_id)) else {
+                    continue;
+                };
+
+                // Conflicts are declared on either side.
+                if *other_field(self)
+                    && (cmd.get_arg_conflicts_with(a).contains(&b)
+                        || cmd.get_arg_conflicts_with(b).contains(&a))
+                {
+                    return Err(Error::InvalidConfig(
+                        key,
+                        format!(
+                            "{}, along with {other_key} = {}",
+                            !inverted, !other_inverted
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the options, with the defaults of `cfg` for the ones the command line doesn't
+    /// give.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the values in `cfg` is invalid.
+    pub fn with_config(&self, cfg: &GitFile<'_>) -> Result<Args, Error> {
+        let mut args = self.clone();
+
+        for (id, key, inverted, field) in FLAGS {
+            let flag = field(&mut args);
+            if *flag || self.negated.contains(&id) {
+                continue;
+            }
+
+            if let Some(value) = get_config_bool(cfg, key)? {
+                *flag = value != inverted;
+            }
+        }
+
+        args.check_conflicts()?;
+
+        // The ways to quote exclude each other, the command line picks one over the others.
+        if args.reply_count.is_none()
+            && args.quote_lines.is_none()
+            && !(args.quote_all || args.attribution_only)
+        {
+            args.quote_lines = get_config_usize(cfg, "acker.quoteLines")?;
+            if args.quote_lines.is_none() {
+                args.reply_count = get_config_string(cfg, "acker.replyCount")
+                    .map(|c| {
+                        parse_reply_count(&c)
+                            .map_err(|e| Error::InvalidConfig("acker.replyCount", e))
+                    })
+                    .transpose()?;
+            }
+        }
+
+        if args.max_recipients.is_none() {
+            args.max_recipients = get_config_usize(cfg, "acker.maxRecipients")?;
+        }
+
+        if args.input_charset.is_none() {
+            args.input_charset = get_config_string(cfg, "acker.inputCharset")
+                .map(|c| {
+                    parse_charset(&c).map_err(|e| Error::InvalidConfig("acker.inputCharset", e))
+                })
+                .transpose()?;
+        }
+
+        Ok(args)
+    }
+}
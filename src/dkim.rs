@@ -0,0 +1,88 @@
+//! DKIM signature of the replies, for the people sending straight to the list servers, without
+//! a relay signing for them.
+//!
+//! `acker.dkimKey` is the private key, a PKCS#1 PEM file for RSA or the base64 of the raw key
+//! for Ed25519, along with `acker.dkimSelector`, the name of the record published in DNS.
+//! `acker.dkimAlgorithm` is `rsa`, the default, or `ed25519`, and `acker.dkimDomain` the
+//! domain to sign for, the one of the From address by default.
+
+use gix_config::{path::interpolate::Context as PathContext, File as GitFile};
+use lettre::{
+    message::{
+        dkim::{
+            DkimCanonicalization, DkimCanonicalizationType, DkimConfig, DkimSigningAlgorithm,
+            DkimSigningKey,
+        },
+        header::HeaderName,
+    },
+    Message,
+};
+
+use crate::{get_config_string, Error};
+
+/// The headers covered by the signature, the ones that tell what the reply is and where it
+/// belongs in the thread.
+const SIGNED_HEADERS: [&str; 9] = [
+    "From",
+    "Sender",
+    "To",
+    "Cc",
+    "Subject",
+    "Date",
+    "Message-ID",
+    "In-Reply-To",
+    "References",
+];
+
+/// Returns the DKIM configuration to sign the replies from `domain` with, if there's a key.
+pub(crate) fn get_config(cfg: &GitFile<'_>, domain: &str) -> Result<Option<DkimConfig>, Error> {
+    let Some(path) = cfg.path_by_key("acker.dkimKey") else {
+        return Ok(None);
+    };
+
+    let path = path
+        .interpolate(PathContext {
+            home_dir: home::home_dir().as_deref(),
+            ..Default::default()
+        })
+        .map_err(|e| Error::InvalidConfig("acker.dkimKey", e.to_string()))?
+        .into_owned();
+
+    let algorithm = match get_config_string(cfg, "acker.dkimAlgorithm").as_deref() {
+        None | Some("rsa") => DkimSigningAlgorithm::Rsa,
+        Some("ed25519") => DkimSigningAlgorithm::Ed25519,
+        Some(a) => return Err(Error::InvalidConfig("acker.dkimAlgorithm", a.to_string())),
+    };
+
+    let key = std::fs::read_to_string(&path).map_err(|e| Error::Io(path.clone(), e))?;
+    let key = DkimSigningKey::new(key.trim(), algorithm)
+        .map_err(|e| Error::InvalidConfig("acker.dkimKey", format!("{}: {e}", path.display())))?;
+
+    let selector = get_config_string(cfg, "acker.dkimSelector")
+        .ok_or(Error::MissingConfig("acker.dkimSelector"))?;
+    let domain = get_config_string(cfg, "acker.dkimDomain").unwrap_or_else(|| domain.to_string());
+
+    // Mailing lists tend to rewrap the headers and touch the whitespace, relaxed survives it.
+    Ok(Some(DkimConfig::new(
+        selector,
+        domain,
+        key,
+        SIGNED_HEADERS
+            .into_iter()
+            .map(HeaderName::new_from_ascii_str)
+            .collect(),
+        DkimCanonicalization {
+            header: DkimCanonicalizationType::Relaxed,
+            body: DkimCanonicalizationType::Relaxed,
+        },
+    )))
+}
+
+/// Signs `eml` with the key of the configuration, if any, for `domain`.
+pub(crate) fn sign(cfg: &GitFile<'_>, eml: &mut Message, domain: &str) -> Result<(), Error> {
+    if let Some(config) = get_config(cfg, domain)? {
+        eml.sign(&config);
+    }
+
+    Ok(())
+}
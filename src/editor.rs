@@ -0,0 +1,211 @@
+//! Interactive edition of parts of the reply through the user's editor.
+
+use std::{
+    fmt::Write as _,
+    io::Write as _,
+    path::PathBuf,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use gix_config::File as GitFile;
+use lettre::message::{Mailbox, Mailboxes};
+
+use crate::{get_config_string, prompt, Draft, Error, Recipients};
+
+/// Returns the editor to use, following the same order than git.
+fn get_editor(cfg: &GitFile<'_>) -> String {
+    std::env::var("GIT_EDITOR")
+        .ok()
+        .or_else(|| get_config_string(cfg, "core.editor"))
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .unwrap_or_else(|| String::from("vi"))
+}
+
+/// Opens `content` in the user's editor and returns what was saved.
+pub(crate) fn edit(cfg: &GitFile<'_>, content: &str) -> Result<String, Error> {
+    let mut file = tempfile::Builder::new()
+        .prefix("acker-")
+        .suffix(".txt")
+        .tempfile()
+        .map_err(|e| Error::Io(std::env::temp_dir(), e))?;
+    let path = file.path().to_path_buf();
+
+    file.write_all(content.as_bytes())
+        .map_err(|e| Error::Io(path.clone(), e))?;
+
+    let editor = get_editor(cfg);
+
+    // stdin might have held the message, the editor gets the terminal instead. Without one, it
+    // can only be one that doesn't need it, so let it have stdin.
+    let stdin = match prompt::open_tty() {
+        Ok(Some(tty)) => Stdio::from(tty),
+        Ok(None) | Err(_) => Stdio::inherit(),
+    };
+
+    // Just like git, let the shell deal with editors that have arguments.
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$@\""))
+        .arg(&editor)
+        .arg(&path)
+        .stdin(stdin)
+        .status()
+        .map_err(|e| Error::Io(PathBuf::from(&editor), e))?;
+
+    if !status.success() {
+        return Err(Error::Editor(editor, status.to_string()));
+    }
+
+    std::fs::read_to_string(&path).map_err(|e| Error::Io(path, e))
+}
+
+/// Lets the user review the recipients of the reply, and returns the edited list.
+pub(crate) fn edit_recipients(
+    cfg: &GitFile<'_>,
+    recipients: &Recipients,
+) -> Result<Recipients, Error> {
+    let mut content = String::from(
+        "# Edit the recipients of the reply, one per line, in the To:, Cc: or Bcc: form.\n\
+         # Lines starting with '#' are ignored, and removing a line drops the recipient.\n",
+    );
+
+    for (field, list) in [
+        ("To", &recipients.to),
+        ("Cc", &recipients.cc),
+        ("Bcc", &recipients.bcc),
+    ] {
+        for mailbox in list {
+            let _ = writeln!(content, "{field}: {mailbox}");
+        }
+    }
+
+    let mut edited = Recipients::default();
+    for line in edit(cfg, &content)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((field, address)) = line.split_once(':') else {
+            eprintln!("acker: warning: ignoring malformed recipient line: {line}");
+            continue;
+        };
+
+        let list = match field.trim().to_ascii_lowercase().as_str() {
+            "to" => &mut edited.to,
+            "cc" => &mut edited.cc,
+            "bcc" => &mut edited.bcc,
+            _ => {
+                eprintln!("acker: warning: ignoring unknown recipient field: {line}");
+                continue;
+            }
+        };
+
+        match Mailbox::from_str(address.trim()) {
+            Ok(mailbox) => list.push(mailbox),
+            Err(_) => eprintln!(
+                "acker: warning: ignoring invalid address: {}",
+                address.trim()
+            ),
+        }
+    }
+
+    Ok(edited)
+}
+
+fn format_mailboxes(list: &[Mailbox]) -> String {
+    list.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_mailboxes(field: &'static str, value: &str) -> Result<Vec<Mailbox>, Error> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Mailboxes::from_str(value)
+        .map(|m| m.into_iter().collect())
+        .map_err(|_| Error::InvalidAddress(field, value.to_string()))
+}
+
+/// Formats the reply the way the user gets to review and edit it.
+pub(crate) fn format_draft(draft: &Draft) -> String {
+    let mut content = String::new();
+    let _ = writeln!(content, "From: {}", draft.from);
+    if let Some(sender) = &draft.sender {
+        let _ = writeln!(content, "Sender: {sender}");
+    }
+    for (field, list) in [
+        ("To", &draft.recipients.to),
+        ("Cc", &draft.recipients.cc),
+        ("Bcc", &draft.recipients.bcc),
+    ] {
+        if !list.is_empty() {
+            let _ = writeln!(content, "{field}: {}", format_mailboxes(list));
+        }
+    }
+    let _ = writeln!(content, "Subject: {}\n", draft.subject);
+    content.push_str(&draft.body);
+
+    content
+}
+
+/// Lets the user edit the whole reply, and returns what was saved. Just like git commit, leaving
+/// the reply unchanged or removing everything aborts.
+pub(crate) fn edit_reply(cfg: &GitFile<'_>, draft: &Draft) -> Result<Draft, Error> {
+    let content = format_draft(draft);
+
+    let edited = edit(cfg, &content)?;
+    if edited == content || edited.trim().is_empty() {
+        return Err(Error::EditAborted);
+    }
+
+    let (headers, body) = edited.split_once("\n\n").unwrap_or((edited.as_str(), ""));
+    if body.trim().is_empty() {
+        return Err(Error::EditAborted);
+    }
+
+    let mut from = None;
+    let mut sender = None;
+    let mut subject = String::new();
+    let mut recipients = Recipients::default();
+    for line in headers.lines() {
+        let Some((field, value)) = line.split_once(':') else {
+            eprintln!("acker: warning: ignoring malformed header line: {line}");
+            continue;
+        };
+
+        match field.trim().to_ascii_lowercase().as_str() {
+            "from" => {
+                from = Some(
+                    Mailbox::from_str(value.trim())
+                        .map_err(|_| Error::InvalidAddress("From", value.trim().to_string()))?,
+                );
+            }
+            "sender" => {
+                sender = Some(
+                    Mailbox::from_str(value.trim())
+                        .map_err(|_| Error::InvalidAddress("Sender", value.trim().to_string()))?,
+                );
+            }
+            "to" => recipients.to.extend(parse_mailboxes("To", value)?),
+            "cc" => recipients.cc.extend(parse_mailboxes("Cc", value)?),
+            "bcc" => recipients.bcc.extend(parse_mailboxes("Bcc", value)?),
+            "subject" => subject = value.trim().to_string(),
+            _ => eprintln!("acker: warning: ignoring unknown header: {line}"),
+        }
+    }
+
+    Ok(Draft {
+        from: from.ok_or(Error::InvalidAddress("From", String::new()))?,
+        sender,
+        subject,
+        recipients,
+        body: body.to_string(),
+    })
+}
@@ -0,0 +1,117 @@
+//! Copy of the sent replies to the local mail store, either a Maildir or an mbox.
+
+use std::{
+    fs::OpenOptions,
+    io::Write as _,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+use lettre::Message;
+use mail_parser::DateTime;
+
+use crate::Error;
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Number of messages delivered by this process, to keep the Maildir file names unique.
+static DELIVERIES: AtomicUsize = AtomicUsize::new(0);
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or_default())
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+        .map(|h| h.trim().replace(['/', ':'], "_"))
+        .ok()
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| String::from("localhost"))
+}
+
+/// A path is a Maildir if it's a directory already, or if it's meant to become one.
+fn is_maildir(path: &Path) -> bool {
+    path.is_dir() || path.as_os_str().to_string_lossy().ends_with('/')
+}
+
+/// Delivers `content` to the Maildir at `path`, flagged as seen since we wrote it.
+fn write_maildir(path: &Path, content: &[u8]) -> Result<(), Error> {
+    for sub in ["tmp", "new", "cur"] {
+        let dir = path.join(sub);
+        std::fs::create_dir_all(&dir).map_err(|e| Error::Io(dir, e))?;
+    }
+
+    let name = format!(
+        "{}.P{}Q{}.{}",
+        now(),
+        std::process::id(),
+        DELIVERIES.fetch_add(1, Ordering::Relaxed),
+        hostname()
+    );
+
+    // Just like any MDA, go through tmp so that readers never see half a message.
+    let tmp = path.join("tmp").join(&name);
+    std::fs::write(&tmp, content).map_err(|e| Error::Io(tmp.clone(), e))?;
+
+    let cur = path.join("cur").join(format!("{name}:2,S"));
+    std::fs::rename(&tmp, &cur).map_err(|e| Error::Io(cur, e))
+}
+
+/// Appends `content` to the mbox at `path`, escaping the lines that would start a new message.
+fn write_mbox(path: &Path, sender: &str, content: &[u8]) -> Result<(), Error> {
+    let date = DateTime::from_timestamp(now());
+    let mut entry = format!(
+        "From {sender} {} {} {:2} {:02}:{:02}:{:02} {}\n",
+        WEEKDAYS[usize::from(date.day_of_week()) % 7],
+        MONTHS[usize::from(date.month.clamp(1, 12)) - 1],
+        date.day,
+        date.hour,
+        date.minute,
+        date.second,
+        date.year
+    )
+    .into_bytes();
+
+    for line in String::from_utf8_lossy(content).lines() {
+        if line.trim_start_matches('>').starts_with("From ") {
+            entry.push(b'>');
+        }
+
+        entry.extend_from_slice(line.as_bytes());
+        entry.push(b'\n');
+    }
+    entry.push(b'\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(&entry))
+        .map_err(|e| Error::Io(path.to_path_buf(), e))
+}
+
+/// Saves a copy of `eml`, as sent, to the Maildir or the mbox at `path`.
+pub(crate) fn save(path: &Path, eml: &Message) -> Result<(), Error> {
+    // Local mail stores use plain line breaks, the CRLF is for the wire.
+    let content = String::from_utf8_lossy(&eml.formatted())
+        .replace("\r\n", "\n")
+        .into_bytes();
+
+    if is_maildir(path) {
+        return write_maildir(path, &content);
+    }
+
+    let sender = eml
+        .envelope()
+        .from()
+        .map_or_else(|| String::from("MAILER-DAEMON"), ToString::to_string);
+
+    write_mbox(path, &sender, &content)
+}
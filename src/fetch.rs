@@ -0,0 +1,104 @@
+//! Download of the messages to reply to from a public-inbox archive, such as lore.kernel.org.
+
+use std::{fmt::Write, io::Read, sync::Arc, time::Duration};
+
+use flate2::read::GzDecoder;
+use gix_config::File as GitFile;
+use ureq::{
+    native_tls::{self, TlsConnector},
+    Agent,
+};
+
+use crate::{get_config_string, Error, DEFAULT_LINK_BASE};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// The suffixes public-inbox adds to the URL of a message for its various views.
+const VIEW_SUFFIXES: &[&str] = &["/raw", "/t.mbox.gz", "/T", "/t"];
+
+/// Percent-encodes everything but the unreserved characters of URLs, and the `@` of Message-IDs.
+pub(crate) fn escape_message_id(id: &str) -> String {
+    id.bytes().fold(String::new(), |mut escaped, b| {
+        if b.is_ascii_alphanumeric() || b"-._~@".contains(&b) {
+            escaped.push(char::from(b));
+        } else {
+            let _ = write!(escaped, "%{b:02X}");
+        }
+
+        escaped
+    })
+}
+
+/// Returns the HTTP client to talk to the archives and the other web services.
+pub(crate) fn http_agent() -> Result<Agent, native_tls::Error> {
+    Ok(ureq::AgentBuilder::new()
+        .tls_connector(Arc::new(TlsConnector::new()?))
+        .timeout(TIMEOUT)
+        .user_agent(concat!("acker/", env!("CARGO_PKG_VERSION")))
+        .build())
+}
+
+/// Turns an error of ureq into a message. Its own repeats the URL we already give.
+pub(crate) fn describe_error(e: ureq::Error) -> String {
+    match e {
+        ureq::Error::Status(code, response) => format!("{code} {}", response.status_text()),
+        ureq::Error::Transport(t) => t
+            .message()
+            .map_or_else(|| t.kind().to_string(), |m| format!("{}: {m}", t.kind())),
+    }
+}
+
+/// Returns the URL to download `target` from, `target` being either the URL of a message in the
+/// archive or its Message-ID. With `thread`, it's the URL of the mbox of the whole thread.
+pub(crate) fn get_url(cfg: &GitFile<'_>, target: &str, thread: bool) -> String {
+    let target = target.trim();
+    let message = if target.starts_with("https://") || target.starts_with("http://") {
+        let mut url = target
+            .split(['#', '?'])
+            .next()
+            .unwrap_or(target)
+            .trim_end_matches('/');
+
+        for suffix in VIEW_SUFFIXES {
+            url = url.strip_suffix(suffix).unwrap_or(url);
+        }
+
+        url.trim_end_matches('/').to_string()
+    } else {
+        let base = get_config_string(cfg, "acker.linkbase")
+            .unwrap_or_else(|| String::from(DEFAULT_LINK_BASE));
+        let id = target.trim_start_matches('<').trim_end_matches('>');
+
+        format!("{}/{}", base.trim_end_matches('/'), escape_message_id(id))
+    };
+
+    format!("{message}/{}", if thread { "t.mbox.gz" } else { "raw" })
+}
+
+/// Downloads `url`, uncompressing it if it's gzip'ed, like the mbox of a thread.
+pub(crate) fn fetch(url: &str) -> Result<Vec<u8>, Error> {
+    let err = |e: &dyn std::fmt::Display| Error::Fetch(url.to_string(), e.to_string());
+
+    let mut body = Vec::new();
+    http_agent()
+        .map_err(|e| err(&e))?
+        .get(url)
+        .call()
+        .map_err(|e| err(&describe_error(e)))?
+        .into_reader()
+        .read_to_end(&mut body)
+        .map_err(|e| err(&e))?;
+
+    if !body.starts_with(GZIP_MAGIC) {
+        return Ok(body);
+    }
+
+    let mut mbox = Vec::new();
+    GzDecoder::new(body.as_slice())
+        .read_to_end(&mut mbox)
+        .map_err(|e| err(&e))?;
+
+    Ok(mbox)
+}
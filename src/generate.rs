@@ -0,0 +1,47 @@
+//! `acker completions` and `acker man`: the shell completions and the manual pages, generated
+//! from the definition of the command line so that they never fall behind it.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap_complete::Shell;
+use clap_mangen::Man;
+
+use crate::{cli_command, Error};
+
+fn print(content: &[u8]) -> Result<ExitCode, Error> {
+    std::io::stdout()
+        .write_all(content)
+        .map_err(|e| Error::Io(PathBuf::from("<stdout>"), e))?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// `acker completions`: prints the completions of `acker` for `shell`.
+pub(crate) fn completions(shell: Shell) -> Result<ExitCode, Error> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut cli_command(), "acker", &mut script);
+
+    print(&script)
+}
+
+/// `acker man`: prints the manual page of `acker`, or writes it to `dir` along with the ones of
+/// its commands.
+pub(crate) fn man(dir: Option<&Path>) -> Result<ExitCode, Error> {
+    let cmd = cli_command();
+
+    if let Some(dir) = dir {
+        clap_mangen::generate_to(cmd, dir).map_err(|e| Error::Io(dir.to_path_buf(), e))?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut page = Vec::new();
+    Man::new(cmd)
+        .render(&mut page)
+        .map_err(|e| Error::Io(PathBuf::from("<stdout>"), e))?;
+
+    print(&page)
+}
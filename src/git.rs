@@ -0,0 +1,26 @@
+//! Running git in the repository acker is invoked from.
+
+use std::{path::PathBuf, process::Command};
+
+use crate::Error;
+
+pub(crate) const GIT: &str = "git";
+
+/// How many commits we look at to find the ones our patches landed as.
+pub(crate) const MAX_COMMITS: &str = "1000";
+
+/// Runs git with `args`, and returns what it printed.
+pub(crate) fn run(args: &[&str]) -> Result<String, Error> {
+    let output = Command::new(GIT)
+        .args(args)
+        .output()
+        .map_err(|e| Error::Io(PathBuf::from(GIT), e))?;
+
+    if !output.status.success() {
+        return Err(Error::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
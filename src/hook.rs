@@ -0,0 +1,63 @@
+//! The commands of `acker.preSendHook` and `acker.postSendHook`, run around each reply we send.
+//!
+//! They get the reply as it's sent on stdin, and what they're the most likely to need in the
+//! environment: `ACKER_MESSAGE_ID`, `ACKER_IN_REPLY_TO`, `ACKER_SUBJECT`, `ACKER_TO`, `ACKER_CC`
+//! and `ACKER_BCC`.
+
+use std::{
+    io::Write as _,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use lettre::message::Mailbox;
+
+use crate::{Error, Reply};
+
+fn join(mailboxes: &[Mailbox]) -> String {
+    mailboxes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Runs the hook `command` for `reply`, the answer to the message `in_reply_to`.
+pub(crate) fn run(command: &str, reply: &Reply, in_reply_to: Option<&str>) -> Result<(), Error> {
+    let io_err = |e| Error::Io(PathBuf::from("sh"), e);
+    let draft = &reply.draft;
+    let headers = reply.message.headers();
+
+    // What the hook prints is for the user, stdout is ours for --dry-run.
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env(
+            "ACKER_MESSAGE_ID",
+            headers.get_raw("Message-ID").unwrap_or_default(),
+        )
+        .env(
+            "ACKER_IN_REPLY_TO",
+            in_reply_to.map(|id| format!("<{id}>")).unwrap_or_default(),
+        )
+        .env("ACKER_SUBJECT", &draft.subject)
+        .env("ACKER_TO", join(&draft.recipients.to))
+        .env("ACKER_CC", join(&draft.recipients.cc))
+        .env("ACKER_BCC", join(&draft.recipients.bcc))
+        .stdin(Stdio::piped())
+        .stdout(std::io::stderr())
+        .spawn()
+        .map_err(io_err)?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that doesn't care about the message might not read it at all.
+        let _ = stdin.write_all(&reply.message.formatted());
+    }
+
+    let status = child.wait().map_err(io_err)?;
+    if !status.success() {
+        return Err(Error::Hook(command.to_string(), status.to_string()));
+    }
+
+    Ok(())
+}
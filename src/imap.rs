@@ -0,0 +1,207 @@
+//! Copy of the sent replies in the Sent folder of an IMAP server, for the people who don't keep
+//! their mail in a local Maildir.
+//!
+//! `acker.imapServer` is the server, as `host` or `host:port`, reached over TLS, and
+//! `acker.imapFolder` the folder, `Sent` by default. We log in as `acker.imapUser`, or else as
+//! `sendemail.smtpuser`, with the password of the git credential helpers.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use gix_config::File as GitFile;
+use lettre::Message;
+use ureq::native_tls::{TlsConnector, TlsStream};
+
+use crate::{credential::Credential, get_config_string, Error};
+
+const DEFAULT_PORT: u16 = 993;
+
+const DEFAULT_FOLDER: &str = "Sent";
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub(crate) struct Imap {
+    server: String,
+    host: String,
+    port: u16,
+    user: String,
+    folder: String,
+}
+
+/// Quotes `s` as an IMAP string.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A connection to the server, that sends one command at a time.
+struct Session {
+    stream: BufReader<TlsStream<TcpStream>>,
+    tag: usize,
+}
+
+impl Session {
+    fn connect(host: &str, port: u16) -> Result<Self, String> {
+        let addr = (host, port)
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| format!("{host} has no address"))?;
+        let tcp = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|e| e.to_string())?;
+        tcp.set_read_timeout(Some(TIMEOUT))
+            .map_err(|e| e.to_string())?;
+
+        let tls = TlsConnector::new()
+            .map_err(|e| e.to_string())?
+            .connect(host, tcp)
+            .map_err(|e| e.to_string())?;
+
+        let mut session = Session {
+            stream: BufReader::new(tls),
+            tag: 0,
+        };
+
+        let greeting = session.read_line()?;
+        if !greeting.starts_with("* OK") {
+            return Err(format!("unexpected greeting {greeting}"));
+        }
+
+        Ok(session)
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        let mut line = String::new();
+        if self
+            .stream
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?
+            == 0
+        {
+            return Err(String::from("the server closed the connection"));
+        }
+
+        Ok(line.trim_end().to_string())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(data)
+            .and_then(|()| stream.flush())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Sends the command `cmd`, and returns the tag it was sent with.
+    fn send(&mut self, cmd: &str) -> Result<String, String> {
+        self.tag += 1;
+        let tag = format!("a{}", self.tag);
+
+        self.write(format!("{tag} {cmd}\r\n").as_bytes())?;
+        Ok(tag)
+    }
+
+    /// Waits for the server to be done with the command sent as `tag`.
+    fn wait(&mut self, tag: &str) -> Result<(), String> {
+        loop {
+            let line = self.read_line()?;
+            let Some(status) = line.strip_prefix(tag).and_then(|l| l.strip_prefix(' ')) else {
+                continue;
+            };
+
+            return match status.strip_prefix("OK") {
+                Some(_) => Ok(()),
+                None => Err(status.to_string()),
+            };
+        }
+    }
+
+    fn command(&mut self, cmd: &str) -> Result<(), String> {
+        let tag = self.send(cmd)?;
+        self.wait(&tag)
+    }
+
+    /// Appends `email` to `folder`, flagged as seen.
+    fn append(&mut self, folder: &str, email: &[u8]) -> Result<(), String> {
+        let tag = self.send(&format!(
+            "APPEND {} (\\Seen) {{{}}}",
+            quote(folder),
+            email.len()
+        ))?;
+
+        // The server tells us to go on with the literal, or refuses the command outright.
+        loop {
+            let line = self.read_line()?;
+            if line.starts_with('+') {
+                break;
+            }
+
+            if let Some(status) = line.strip_prefix(&tag).and_then(|l| l.strip_prefix(' ')) {
+                return Err(status.to_string());
+            }
+        }
+
+        self.write(email)?;
+        self.write(b"\r\n")?;
+        self.wait(&tag)
+    }
+}
+
+impl Imap {
+    /// Returns the IMAP server to copy the replies to, if any.
+    pub(crate) fn from_config(cfg: &GitFile<'_>) -> Result<Option<Self>, Error> {
+        let Some(server) = get_config_string(cfg, "acker.imapServer") else {
+            return Ok(None);
+        };
+
+        let (host, port) = match server.rsplit_once(':') {
+            Some((host, port)) => (
+                host.to_string(),
+                port.parse()
+                    .map_err(|_| Error::InvalidConfig("acker.imapServer", server.clone()))?,
+            ),
+            None => (server.clone(), DEFAULT_PORT),
+        };
+
+        let user = get_config_string(cfg, "acker.imapUser")
+            .or_else(|| get_config_string(cfg, "sendemail.smtpuser"))
+            .ok_or(Error::MissingConfig("acker.imapUser"))?;
+
+        Ok(Some(Imap {
+            server,
+            host,
+            port,
+            user,
+            folder: get_config_string(cfg, "acker.imapFolder")
+                .unwrap_or_else(|| String::from(DEFAULT_FOLDER)),
+        }))
+    }
+
+    /// Appends `eml`, flagged as seen, to the folder.
+    pub(crate) fn append(&self, eml: &Message) -> Result<(), Error> {
+        let err = |e| Error::Imap(self.server.clone(), e);
+        let credential = Credential::fill("imaps", &self.server, &self.user)?
+            .ok_or_else(|| err(format!("no password for {}", self.user)))?;
+
+        let mut session = Session::connect(&self.host, self.port).map_err(err)?;
+        if let Err(e) = session.command(&format!(
+            "LOGIN {} {}",
+            quote(credential.username()),
+            quote(credential.password())
+        )) {
+            credential.reject();
+            return Err(err(e));
+        }
+        credential.approve();
+
+        session
+            .append(&self.folder, &eml.formatted())
+            .map_err(err)?;
+
+        // We're done whatever the server thinks of our goodbye.
+        let _ = session.command("LOGOUT");
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+//! `acker init`: interactive creation of the configuration acker needs to send mails.
+
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+    time::Duration,
+};
+
+use gix_config::{File as GitFile, Source};
+use lettre::Address;
+
+use crate::{
+    get_config_string,
+    prompt::{confirm, prompt},
+    Error,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn find_in_path(cmd: &str) -> bool {
+    let path = Path::new(cmd);
+    if path.components().count() > 1 {
+        return path.exists();
+    }
+
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(cmd).exists()))
+}
+
+fn check_smtp_server(host: &str, port: u16) -> bool {
+    let Ok(addrs) = (host, port).to_socket_addrs() else {
+        return false;
+    };
+
+    addrs
+        .into_iter()
+        .any(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok())
+}
+
+/// Sets `key` in the `section` of `file`, asking first if it would override a different value.
+fn set_value(
+    file: &mut GitFile<'static>,
+    section: &'static str,
+    key: &'static str,
+    value: &str,
+) -> Result<(), Error> {
+    let name = format!("{section}.{key}");
+
+    if let Some(current) = get_config_string(file, &name) {
+        if current == value {
+            return Ok(());
+        }
+
+        if !confirm(&format!("Overwrite {name} (currently {current})?"))? {
+            return Ok(());
+        }
+    }
+
+    file.set_raw_value(section, None, key, value)
+        .map_err(|_| Error::InvalidConfig("acker init", name))?;
+
+    Ok(())
+}
+
+fn global_config_path() -> Result<PathBuf, Error> {
+    home::home_dir()
+        .map(|h| h.join(".gitconfig"))
+        .ok_or(Error::MissingConfig("HOME"))
+}
+
+pub(crate) fn run(cfg: &GitFile<'_>) -> Result<ExitCode, Error> {
+    let path = global_config_path()?;
+    let mut file = if path.exists() {
+        GitFile::from_path_no_includes(path.clone(), Source::User)
+            .map_err(|e| Error::Io(path.clone(), std::io::Error::other(e)))?
+    } else {
+        GitFile::default()
+    };
+
+    let name = prompt("Your name", get_config_string(cfg, "user.name").as_deref())?;
+    set_value(&mut file, "user", "name", &name)?;
+
+    let email = loop {
+        let email = prompt(
+            "Your e-mail",
+            get_config_string(cfg, "user.email").as_deref(),
+        )?;
+        if Address::from_str(&email).is_ok() {
+            break email;
+        }
+
+        println!("{email} isn't a valid e-mail address");
+    };
+    set_value(&mut file, "user", "email", &email)?;
+
+    let transport = prompt("Send mails through sendmail or smtp", Some("sendmail"))?;
+    if transport.eq_ignore_ascii_case("smtp") {
+        let host = prompt(
+            "SMTP server",
+            get_config_string(cfg, "sendemail.smtpserver").as_deref(),
+        )?;
+        let encryption = prompt("Encryption (ssl, tls or none)", Some("tls"))?.to_lowercase();
+        let default_port = match encryption.as_str() {
+            "ssl" => "465",
+            "tls" => "587",
+            _ => "25",
+        };
+        let port = prompt("SMTP port", Some(default_port))?;
+        let port_number = u16::from_str(&port)
+            .map_err(|_| Error::InvalidConfig("sendemail.smtpserverport", port.clone()))?;
+        let user = prompt("SMTP user", Some(&email))?;
+
+        if !check_smtp_server(&host, port_number)
+            && !confirm(&format!("Couldn't connect to {host}:{port}, save anyway?"))?
+        {
+            return Ok(ExitCode::FAILURE);
+        }
+
+        set_value(&mut file, "sendemail", "smtpserver", &host)?;
+        set_value(&mut file, "sendemail", "smtpserverport", &port)?;
+        if encryption != "none" {
+            set_value(&mut file, "sendemail", "smtpencryption", &encryption)?;
+        }
+        set_value(&mut file, "sendemail", "smtpuser", &user)?;
+    } else {
+        let cmd = prompt(
+            "sendmail command",
+            Some(
+                get_config_string(cfg, "sendemail.sendmailcmd")
+                    .as_deref()
+                    .unwrap_or("sendmail"),
+            ),
+        )?;
+
+        if !find_in_path(&cmd) && !confirm(&format!("Couldn't find {cmd}, save anyway?"))? {
+            return Ok(ExitCode::FAILURE);
+        }
+
+        set_value(&mut file, "sendemail", "sendmailcmd", &cmd)?;
+    }
+
+    let mut out = std::fs::File::create(&path).map_err(|e| Error::Io(path.clone(), e))?;
+    file.write_to(&mut out)
+        .map_err(|e| Error::Io(path.clone(), e))?;
+
+    println!("Configuration written to {}", path.display());
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,5276 @@
+#![warn(missing_debug_implementations)]
+#![warn(rust_2018_idioms)]
+#![deny(clippy::all)]
+#![deny(clippy::pedantic)]
+#![deny(clippy::cargo)]
+#![allow(clippy::manual_let_else)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Building and sending of review replies to patches received by e-mail.
+//!
+//! The `acker` binary is a thin wrapper around [`run`], but [`ReplyBuilder`] and
+//! [`MailTransport`] can be used on their own by other mail tools, and [`run_with`] runs it with
+//! another configuration and transport.
+
+mod aliases;
+mod applied;
+mod attachment;
+mod batch;
+mod credential;
+mod defaults;
+mod dkim;
+mod editor;
+mod fcc;
+mod fetch;
+mod generate;
+mod git;
+mod hook;
+mod imap;
+mod init;
+mod locale;
+mod mailmap;
+mod maintainers;
+mod notes;
+mod notmuch;
+mod outbox;
+mod output;
+mod patch;
+mod patchwork;
+mod project;
+mod prompt;
+mod quote;
+#[cfg(feature = "tui")]
+mod select;
+mod series;
+mod session;
+mod sign;
+mod state;
+mod stats;
+mod template;
+mod thread;
+mod trace;
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    default::Default,
+    ffi::OsString,
+    fmt::{self, Write as _},
+    io::{Read, Write as _},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::SystemTime,
+};
+
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use gix_config::{path::interpolate::Context as PathContext, File as GitFile};
+use lettre::{
+    address::Envelope,
+    message::{
+        header::{ContentType, HeaderName, HeaderValue},
+        Mailbox, Mailboxes,
+    },
+    transport::smtp::{
+        authentication::{Credentials, Mechanism},
+        AsyncSmtpTransportBuilder,
+    },
+    Address, AsyncSmtpTransport, AsyncTransport, FileTransport, Message, SendmailTransport,
+    Tokio1Executor, Transport,
+};
+use mail_parser::{
+    decoders::{
+        base64::base64_decode, charsets::map::charset_decoder, html::html_to_text,
+        quoted_printable::quoted_printable_decode,
+    },
+    mailbox::{maildir, mbox::MessageIterator},
+    DateTime, MessageParser, MimeHeaders,
+};
+use tracing::{debug, info, trace, Instrument};
+
+const DEFAULT_WRAP_WIDTH: usize = 72;
+const DEFAULT_TRUNCATION_MARKER: &str = "[ ... ]";
+/// What the 8-bit bodies without a charset are decoded as.
+const FALLBACK_CHARSET: &str = "windows-1252";
+const DEFAULT_LINK_BASE: &str = "https://lore.kernel.org/r";
+const DEFAULT_STALE_AFTER_DAYS: usize = 180;
+const DEFAULT_TO_HEADER: &str = "X-Ack-To";
+const DEFAULT_CC_HEADER: &str = "X-Ack-Cc";
+/// How many recipients a reply can have before we suspect a malformed original message.
+const DEFAULT_WARN_RECIPIENTS: usize = 50;
+
+/// Exit code used by `--dry-run --exit-code` when the reply could be fully assembled.
+const EXIT_DRY_RUN_OK: u8 = 10;
+
+/// Everything that can go wrong while replying.
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, std::io::Error),
+    Fetch(String, String),
+    Editor(String, String),
+    Hook(String, String),
+    Sign(String, String),
+    OauthToken(String, String),
+    EditAborted,
+    Aborted,
+    NoTerminal(std::io::Error),
+    InvalidMessage(PathBuf),
+    NoCoverLetter(PathBuf),
+    NoParentMessageId(PathBuf),
+    MissingHeader(&'static str),
+    Config(String),
+    MissingConfig(&'static str),
+    NoSender,
+    NoTransport,
+    InvalidConfig(&'static str, String),
+    InvalidAddress(&'static str, String),
+    InvalidHeaderValue(&'static str, String),
+    InvalidTrailerLine(PathBuf, usize, String),
+    UnsupportedBody,
+    Build(&'static str, lettre::error::Error),
+    Send(String),
+    Patchwork(String, String),
+    Notmuch(String),
+    Imap(String, String),
+    FromDomainMismatch(String, String),
+    NoRecipients,
+    TooManyCc(usize, usize),
+    TooManyRecipients(usize, usize),
+    AlreadyAcked(String, String),
+    InvalidPatch(String),
+    NotInSeries(usize, usize),
+    PatchDoesntApply(String),
+    TestFailed(String, String),
+    Git(String),
+}
+
+impl Error {
+    /// Returns the exit code documented for this kind of error.
+    #[must_use]
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Error::Io(_, _)
+            | Error::Fetch(_, _)
+            | Error::Editor(_, _)
+            | Error::Hook(_, _)
+            | Error::Git(_)
+            | Error::EditAborted
+            | Error::Aborted
+            | Error::NoTerminal(_) => 1,
+            Error::InvalidMessage(_)
+            | Error::NoCoverLetter(_)
+            | Error::NoParentMessageId(_)
+            | Error::MissingHeader(_)
+            | Error::InvalidAddress(_, _)
+            | Error::InvalidTrailerLine(_, _, _)
+            | Error::NotInSeries(_, _)
+            | Error::UnsupportedBody => 3,
+            Error::Config(_)
+            | Error::MissingConfig(_)
+            | Error::NoSender
+            | Error::NoTransport
+            | Error::InvalidConfig(_, _)
+            | Error::OauthToken(_, _)
+            | Error::FromDomainMismatch(_, _) => 4,
+            Error::InvalidHeaderValue(_, _)
+            | Error::Build(_, _)
+            | Error::Sign(_, _)
+            | Error::NoRecipients => 5,
+            Error::TooManyCc(_, _)
+            | Error::TooManyRecipients(_, _)
+            | Error::AlreadyAcked(_, _)
+            | Error::InvalidPatch(_) => 6,
+            Error::Send(_) | Error::Patchwork(_, _) | Error::Notmuch(_) | Error::Imap(_, _) => 7,
+            Error::PatchDoesntApply(_) | Error::TestFailed(_, _) => 8,
+        })
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(p, e) => write!(f, "couldn't access {}: {e}", p.display()),
+            Error::Fetch(url, e) => write!(f, "couldn't download {url}: {e}"),
+            Error::Editor(editor, status) => write!(f, "editor {editor} failed: {status}"),
+            Error::Hook(hook, status) => write!(f, "hook {hook} failed: {status}"),
+            Error::Git(e) => write!(f, "git failed: {e}"),
+            Error::EditAborted => f.write_str("aborting the reply, it was left unchanged or empty"),
+            Error::Aborted => f.write_str("aborted"),
+            Error::NoTerminal(e) => write!(
+                f,
+                "nowhere to ask for an answer, stdin holds the message and the terminal can't be opened: {e}"
+            ),
+            Error::InvalidMessage(p) => write!(f, "{} isn't a valid e-mail", p.display()),
+            Error::NoCoverLetter(p) => write!(f, "couldn't find a cover letter in {}", p.display()),
+            Error::NoParentMessageId(p) => write!(
+                f,
+                "{} has no Message-ID header, the reply can't point at it",
+                p.display()
+            ),
+            Error::MissingHeader(h) => write!(f, "original message has no {h} header"),
+            Error::Config(e) => write!(f, "couldn't read the git configuration: {e}"),
+            Error::MissingConfig(k) => write!(f, "git configuration key {k} is not set"),
+            Error::NoSender => f.write_str(
+                "no address to send from, set user.email (or acker.defaultFrom as a fallback) with git config --global",
+            ),
+            Error::NoTransport => f.write_str(
+                "no way to send the reply, set sendemail.smtpserver to your SMTP server or sendemail.sendmailcmd to a sendmail-like program (acker init helps), or use --transport stdout",
+            ),
+            Error::InvalidConfig(k, v) => write!(f, "invalid value for {k}: {v}"),
+            Error::InvalidAddress(field, a) => write!(f, "invalid e-mail address in {field}: {a}"),
+            Error::InvalidHeaderValue(field, v) => {
+                write!(f, "{field} can't contain a line break: {v:?}")
+            }
+            Error::InvalidTrailerLine(p, line, l) => write!(
+                f,
+                "{}:{line}: expected a \"Trailer-Type: Name <address>\" line, got {l:?}",
+                p.display()
+            ),
+            Error::UnsupportedBody => {
+                f.write_str("original message has no text or HTML body to quote")
+            }
+            Error::NotInSeries(patch, len) => {
+                write!(f, "patch {patch} isn't part of the series, it has {len} patches")
+            }
+            Error::Build(field, e) => write!(f, "couldn't build the {field} of the reply: {e}"),
+            Error::Sign(program, e) => write!(f, "couldn't sign the reply with {program}: {e}"),
+            Error::OauthToken(cmd, e) => write!(f, "couldn't get an OAuth2 token from {cmd}: {e}"),
+            Error::Send(e) => write!(f, "couldn't send the reply: {e}"),
+            Error::Patchwork(url, e) => write!(f, "couldn't update the patch in {url}: {e}"),
+            Error::Notmuch(status) => write!(f, "notmuch failed: {status}"),
+            Error::Imap(server, e) => write!(f, "couldn't copy the reply to {server}: {e}"),
+            Error::NoRecipients => f.write_str("the reply has no recipient left"),
+            Error::TooManyCc(count, max) => write!(
+                f,
+                "the reply would be sent to {count} people in Cc, more than the {max} allowed by acker.maxCc (use --force to send anyway)"
+            ),
+            Error::TooManyRecipients(count, max) => write!(
+                f,
+                "the reply would be sent to {count} people, more than the {max} allowed by --max-recipients or acker.maxRecipients"
+            ),
+            Error::AlreadyAcked(trailer, id) => write!(
+                f,
+                "{trailer} was already given to <{id}> (use --force to send anyway)"
+            ),
+            Error::InvalidPatch(problems) => write!(
+                f,
+                "refusing to tag the patch with --strict: {problems} (use --force to send anyway)"
+            ),
+            Error::PatchDoesntApply(subject) => {
+                write!(f, "\"{subject}\" doesn't apply to the current work tree")
+            }
+            Error::TestFailed(cmd, status) => write!(
+                f,
+                "test command {cmd:?} failed: {status}, not giving a Tested-by"
+            ),
+            Error::FromDomainMismatch(from, user) => write!(
+                f,
+                "sending from {from} as SMTP user {user}, the relay will likely reject or rewrite it (use --force to send anyway)"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Command line of acker: either one of the commands, or the options of `acker reply`.
+#[derive(Parser, Debug)]
+#[command(author, version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Use the sendemail.<IDENTITY>.* configuration, like git send-email --identity
+    #[arg(short, long, value_name = "IDENTITY", global = true)]
+    identity: Option<String>,
+
+    /// Use the From address, transport and settings of the acker.<PROFILE>.* configuration
+    #[arg(long, value_name = "PROFILE", global = true)]
+    profile: Option<String>,
+
+    /// Print the dialogue with the SMTP server, with the credentials masked
+    #[arg(long = "trace-smtp", global = true)]
+    trace_smtp: bool,
+
+    /// Send the replies through TRANSPORT, rather than the one the configuration picks
+    #[arg(long, value_name = "TRANSPORT", value_enum, global = true)]
+    transport: Option<TransportKind>,
+
+    /// Tell what acker does: how the transport and the recipients were chosen with -v, along
+    /// with the configuration read and the SMTP dialogue with -vv, and more with -vvv
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    #[command(flatten)]
+    reply: Args,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Reply to the messages with the review tags, what acker does without a command
+    Reply(Args),
+    /// Download a message from the archive of acker.linkbase and reply to it
+    Fetch {
+        /// URL or Message-ID of the message
+        #[arg(value_name = "URL|MSGID")]
+        target: String,
+
+        #[command(flatten)]
+        reply: Args,
+    },
+    /// Send the replies queued with --queue
+    #[command(alias = "flush")]
+    SendQueue,
+    /// Record the trailers given to the patches as git notes on the commits they landed as
+    Notes {
+        /// Commits to look at
+        #[arg(value_name = "REVISION", default_value = "HEAD")]
+        revision: String,
+
+        /// Point at the patches in the archive of acker.linkbase with a Link line, rather than
+        /// with their Message-ID
+        #[arg(long)]
+        link: bool,
+    },
+    /// Interactively set up the git configuration needed to send replies
+    Init,
+    /// Count the tags we gave, by week and by mailing list
+    Stats {
+        /// Only count the tags of the last AGE, such as 2weeks or 3months
+        #[arg(long, value_name = "AGE", value_parser = stats::parse_since)]
+        since: Option<u64>,
+    },
+    /// Print the completions of acker for SHELL
+    Completions {
+        #[arg(value_name = "SHELL")]
+        shell: clap_complete::Shell,
+    },
+    /// Print the manual page of acker, or write the ones of acker and its commands to DIR
+    Man {
+        #[arg(value_name = "DIR")]
+        dir: Option<PathBuf>,
+    },
+}
+
+/// Options of `acker reply`.
+#[derive(Clone, Parser, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Args {
+    /// Message to reply to, or mbox or Maildir with the messages to reply to, - for stdin
+    #[arg(value_name = "FILE", default_value = "-")]
+    input: PathBuf,
+
+    /// Download the message to reply to from the archive of acker.linkbase instead, from its
+    /// URL or its Message-ID
+    #[arg(long, value_name = "URL|MSGID", conflicts_with = "input")]
+    fetch: Option<String>,
+
+    /// Reply to all the patches of a thread, but not to the reviews in it. With --fetch, download
+    /// the whole thread
+    #[arg(long)]
+    thread: bool,
+
+    /// Print the replies the message already got before replying, from the thread in this mbox
+    /// or Maildir, or else from the archive of acker.linkbase
+    #[arg(
+        long = "show-thread",
+        value_name = "PATH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ""
+    )]
+    show_thread: Option<PathBuf>,
+
+    /// Skip the messages we already replied to, or that someone already gave the same tags to
+    /// in the thread, rather than only warning about them. Downloads the thread from the archive
+    /// unless --show-thread gives it
+    #[arg(long = "skip-if-tagged")]
+    skip_if_tagged: bool,
+
+    /// Pick up the review of a series where it stopped: skip the patches we already replied to
+    /// in an earlier run, and send each reply as soon as it's ready
+    #[arg(long)]
+    resume: bool,
+
+    #[arg(short, long)]
+    acked: bool,
+
+    /// Reply that the patch was applied to BRANCH, the current one by default, with the commit it
+    /// landed as, rather than giving tags
+    #[arg(
+        long,
+        value_name = "BRANCH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "",
+        conflicts_with_all = ["acked", "reviewed", "tested", "signed_off", "trailers"]
+    )]
+    applied: Option<String>,
+
+    /// Reply that the patch shouldn't be merged, with a Nacked-by trailer rather than the tags.
+    /// The reasons are given with --comment, or written with --edit
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "acked", "reviewed", "tested", "signed_off", "trailers", "applied", "patch_tags",
+            "on_behalf_of"
+        ]
+    )]
+    nack: bool,
+
+    /// Choose the tags of each patch of the series on a screen listing them, starting from the
+    /// ones given on the command line, before sending all the replies. The series has to come
+    /// from a file or from --fetch, since the choices are read on stdin
+    #[cfg(feature = "tui")]
+    #[arg(
+        long,
+        conflicts_with_all = ["applied", "nack", "patch_tags", "cover_letter", "comments"]
+    )]
+    select: bool,
+
+    #[arg(short = 'n', long = "dry-run")]
+    dry: bool,
+
+    /// Store the replies in the outbox, for acker send-queue to send them later
+    #[arg(long, conflicts_with = "dry")]
+    queue: bool,
+
+    /// Print the replies, Bcc included, for the mail client that piped the message to us to
+    /// send them, rather than sending them ourselves
+    #[arg(long, conflicts_with_all = ["dry", "queue", "confirm"])]
+    compose: bool,
+
+    /// How to print the replies with --dry-run
+    #[arg(
+        long = "output-format",
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t
+    )]
+    output_format: output::OutputFormat,
+
+    /// Send the reply even if sanity checks failed
+    #[arg(short, long)]
+    force: bool,
+
+    /// Refuse to send if the patch fails its sanity checks, rather than only warning
+    #[arg(long)]
+    strict: bool,
+
+    /// With --dry-run, exit with code 10 if the reply could be fully assembled
+    #[arg(long = "exit-code")]
+    exit_code: bool,
+
+    #[arg(short, long)]
+    reviewed: bool,
+
+    #[arg(short, long)]
+    tested: bool,
+
+    /// With --tested, apply the patches to the current git work tree and run this command on
+    /// them first, rather than the one of acker.testCmd. The Tested-by is only given if it passes
+    #[arg(long = "test-cmd", value_name = "COMMAND", requires = "tested")]
+    test_cmd: Option<String>,
+
+    /// Refuse to send if the patches don't apply to the current git work tree
+    #[arg(long = "check-apply")]
+    check_apply: bool,
+
+    /// Mention the test command in a comment after the Tested-by
+    #[arg(long = "record-test-cmd", requires = "tested")]
+    record_test_cmd: bool,
+
+    #[arg(short, long = "signed-off")]
+    signed_off: bool,
+
+    /// Quote the original From, To, Cc, Date and Subject headers before the body
+    #[arg(long = "quote-headers")]
+    quote_headers: bool,
+
+    /// Only reply to the cover letter of the series, with tags meant for the whole series
+    #[arg(long = "cover-letter")]
+    cover_letter: bool,
+
+    /// Reply to the cover letter with the tags given to some patches only, such as 1-3:reviewed
+    /// or 2,4:acked,tested. Can be repeated
+    #[arg(
+        long = "for",
+        value_name = "PATCHES:TAGS",
+        value_parser = parse_patch_tags,
+        conflicts_with = "applied"
+    )]
+    patch_tags: Vec<PatchTags>,
+
+    /// Thread the reply under the message stored in this file rather than the original one
+    #[arg(long, value_name = "FILE")]
+    parent: Option<PathBuf>,
+
+    /// Send the reply from this address, such as the alias of a team, with our own in the
+    /// Sender header. Overrides acker.from
+    #[arg(long, value_name = "ADDRESS")]
+    from: Option<Mailbox>,
+
+    /// Also send the reply to this address, or alias of sendemail.aliasesfile. Can be repeated
+    #[arg(long, value_name = "ADDRESS")]
+    to: Vec<String>,
+
+    /// Also Cc this address, or alias of sendemail.aliasesfile, on top of the ones of acker.cc.
+    /// Can be repeated
+    #[arg(long, value_name = "ADDRESS")]
+    cc: Vec<String>,
+
+    /// Also Bcc this address, or alias of sendemail.aliasesfile, on top of the ones of
+    /// acker.bcc. Can be repeated
+    #[arg(long, value_name = "ADDRESS")]
+    bcc: Vec<String>,
+
+    /// Save a copy of the sent reply to this Maildir or mbox, overriding acker.fcc
+    #[arg(long, value_name = "PATH")]
+    fcc: Option<PathBuf>,
+
+    /// Sign the reply with gpg --clearsign, with the key of user.signingkey
+    #[arg(long)]
+    sign: bool,
+
+    /// Sign the reply with S/MIME, with the certificate of acker.smimeCert and the key of
+    /// acker.smimeKey
+    #[arg(long, conflicts_with = "sign")]
+    smime: bool,
+
+    /// Don't append the signature to the reply
+    #[arg(long = "no-signature")]
+    no_signature: bool,
+
+    /// Don't Cc ourselves
+    #[arg(long = "no-cc-self")]
+    no_cc_self: bool,
+
+    /// Categories of people to leave out of the Cc list (self, author, cc or all), overriding
+    /// sendemail.suppresscc
+    #[arg(
+        long = "suppress-cc",
+        value_name = "CATEGORY",
+        value_delimiter = ',',
+        value_parser = parse_suppress_cc
+    )]
+    suppress_cc: Vec<SuppressCc>,
+
+    /// Also send the reply to the recipients listed in the acker.toHeader and acker.ccHeader
+    /// headers of the original message
+    #[arg(long = "respect-headers")]
+    respect_headers: bool,
+
+    /// Cc the maintainers and lists `scripts/get_maintainer.pl`, or `acker.getMaintainer`, finds for
+    /// the patch
+    #[arg(long = "auto-cc")]
+    auto_cc: bool,
+
+    /// Cc the people listed in the Cc: trailers of the commit message, as git send-email does
+    #[arg(long = "cc-trailers")]
+    cc_trailers: bool,
+
+    /// Leave the addresses matching PATTERN out of the Cc list, on top of the ones of
+    /// acker.ccBlock. Can be repeated
+    #[arg(long = "drop-cc", value_name = "PATTERN")]
+    drop_cc: Vec<String>,
+
+    /// Refuse to send the reply to more than N people, To, Cc and Bcc together, even with
+    /// --force
+    #[arg(long = "max-recipients", value_name = "N")]
+    max_recipients: Option<usize>,
+
+    /// Reply to the sender, even if the original message asks otherwise with its Reply-To or
+    /// Mail-Followup-To headers
+    #[arg(long = "ignore-reply-to")]
+    ignore_reply_to: bool,
+
+    /// Start a new thread, without In-Reply-To and References headers
+    #[arg(long = "no-references", conflicts_with = "parent")]
+    no_references: bool,
+
+    /// Print on stderr the trailers that were added, as tab-separated "trailer", key and value
+    #[arg(long = "report-trailers")]
+    report_trailers: bool,
+
+    /// Decode the original body with this charset, whatever charset it declares
+    #[arg(long = "input-charset", value_name = "CHARSET", value_parser = parse_charset)]
+    input_charset: Option<String>,
+
+    /// Show the reply and ask for confirmation before sending it
+    #[arg(long)]
+    confirm: bool,
+
+    /// Edit the reply in an editor before sending it
+    #[arg(short, long)]
+    edit: bool,
+
+    /// Review and edit the recipients of the reply in an editor before sending
+    #[arg(long = "confirm-recipients")]
+    confirm_recipients: bool,
+
+    /// Add a Link trailer pointing at the original message in the list archives
+    #[arg(long)]
+    link: bool,
+
+    /// Additional trailer to add, in the KEY=VALUE form, or KEY to give it with our identity. Can
+    /// be repeated.
+    #[arg(long = "trailer", value_name = "KEY[=VALUE]")]
+    trailers: Vec<Trailer>,
+
+    /// Scope the trailers given before, since the previous --comment, with a "# COMMENT" such as
+    /// "for the DT bits". Can be repeated
+    #[arg(long = "comment", value_name = "COMMENT", value_parser = parse_comment)]
+    comments: Vec<String>,
+
+    /// Position on the command line of each occurrence of the trailer arguments.
+    #[arg(skip)]
+    trailer_indices: HashMap<&'static str, Vec<usize>>,
+
+    /// The flags the command line turned off, or that conflict with its options, whatever the
+    /// configuration says.
+    #[arg(skip)]
+    negated: Vec<&'static str>,
+
+    /// Also add the trailers listed in this file, one "Trailer-Type: Name <address>" per line,
+    /// on behalf of the people they name
+    #[arg(long = "on-behalf-of", value_name = "FILE")]
+    on_behalf_of: Option<PathBuf>,
+
+    /// Greet and sign off with the strings of this [acker.locale "LOCALE"] section, rather than
+    /// the one matching the recipient's domain
+    #[arg(long, value_name = "LOCALE")]
+    locale: Option<String>,
+
+    /// Quote the first N lines of each paragraph of the original message, rather than its whole
+    /// commit message
+    #[arg(long = "reply-count", value_name = "N", value_parser = parse_reply_count)]
+    reply_count: Option<usize>,
+
+    /// Only write the attribution line, without quoting the original message
+    #[arg(
+        long = "attribution-only",
+        conflicts_with_all = ["quote_headers", "reply_count", "echo_trailers", "quote_all", "quote_lines"]
+    )]
+    attribution_only: bool,
+
+    /// Quote the whole original message, diff included, for an inline review
+    #[arg(long = "quote-all", conflicts_with_all = ["quote_lines", "reply_count"])]
+    quote_all: bool,
+
+    /// Quote the first N lines of the original message, diff included, for an inline review
+    #[arg(long = "quote-lines", value_name = "N", conflicts_with = "reply_count")]
+    quote_lines: Option<usize>,
+
+    /// Quote the diffstat of the patch after its commit message, regenerated from its diff if
+    /// the patch has none
+    #[arg(long = "with-diffstat", conflicts_with_all = ["attribution_only", "quote_all"])]
+    with_diffstat: bool,
+
+    /// Copy the trailers of the original message at the end of the quote
+    #[arg(long = "echo-trailers")]
+    echo_trailers: bool,
+
+    /// Reply to the patch author from the in-body From: line rather than to the sender
+    #[arg(long = "use-patch-author")]
+    use_patch_author: bool,
+
+    /// Only send the reply to the patch author, for private feedback: no Cc, and none of the
+    /// recipients the original message asks for besides the ones given with --to, --cc and --bcc
+    #[arg(long = "author-only", conflicts_with_all = ["respect_headers", "auto_cc", "cc_trailers"])]
+    author_only: bool,
+
+    /// Date to use for the reply instead of the current time, in RFC 2822 format
+    #[arg(long, hide = true, value_parser = parse_date)]
+    date: Option<DateTime>,
+}
+
+/// The arguments adding trailers, whose order on the command line we keep.
+const TRAILER_ARGS: [&str; 5] = ["acked", "reviewed", "tested", "signed_off", "trailers"];
+
+/// The argument scoping the trailers given before it.
+const COMMENT_ARG: &str = "comments";
+
+/// Returns the definition of the whole command line, with the flags undoing the configuration.
+fn cli_command() -> clap::Command {
+    defaults::add_negations(Cli::command())
+        .mut_subcommand("reply", defaults::add_negations)
+        .mut_subcommand("fetch", defaults::add_negations)
+}
+
+impl Cli {
+    /// Parses the command line like [`Args::try_parse_ordered`] does, whether the reply options
+    /// are given to `acker` itself or to one of its commands.
+    ///
+    /// # Errors
+    ///
+    /// Returns the clap error to report if the command line is invalid.
+    pub fn try_parse_ordered<I, T>(itr: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = cli_command().try_get_matches_from(itr)?;
+        let mut cli = Cli::from_arg_matches(&matches)?;
+
+        match (&mut cli.command, matches.subcommand()) {
+            (Some(Command::Reply(args) | Command::Fetch { reply: args, .. }), Some((_, sub))) => {
+                args.record_trailer_indices(sub)?;
+                args.record_negations(sub);
+            }
+            (None, _) => {
+                cli.reply.record_trailer_indices(&matches)?;
+                cli.reply.record_negations(&matches);
+            }
+            _ => {}
+        }
+
+        Ok(cli)
+    }
+}
+
+impl Args {
+    /// Parses the command line, remembering where each trailer was given so that they can be
+    /// emitted in the same order.
+    ///
+    /// # Errors
+    ///
+    /// Returns the clap error to report if the command line is invalid.
+    pub fn try_parse_ordered<I, T>(itr: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = defaults::add_negations(Args::command()).try_get_matches_from(itr)?;
+        let mut args = Args::from_arg_matches(&matches)?;
+        args.record_trailer_indices(&matches)?;
+        args.record_negations(&matches);
+
+        Ok(args)
+    }
+
+    fn record_trailer_indices(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        for id in TRAILER_ARGS.into_iter().chain([COMMENT_ARG]) {
+            if let Some(indices) = matches.indices_of(id) {
+                self.trailer_indices.insert(id, indices.collect());
+            }
+        }
+
+        // The comments of a nack are its reasons, they don't scope any trailer.
+        if self.nack {
+            if self.comments.is_empty() && !self.edit {
+                return Err(clap::Error::raw(
+                    clap::error::ErrorKind::MissingRequiredArgument,
+                    "--nack needs the reasons, with --comment or --edit\n",
+                ));
+            }
+
+            return Ok(());
+        }
+
+        let mut start = 0;
+        for &end in self.trailer_indices.get(COMMENT_ARG).into_iter().flatten() {
+            let scoped = TRAILER_ARGS
+                .iter()
+                .filter_map(|id| self.trailer_indices.get(id))
+                .flatten()
+                .any(|i| (start..end).contains(i));
+            if !scoped {
+                return Err(clap::Error::raw(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "--comment must follow the trailers it applies to\n",
+                ));
+            }
+
+            start = end;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the position on the command line of the `nth` occurrence of the argument `id`.
+    fn trailer_index(&self, id: &str, nth: usize) -> usize {
+        self.trailer_indices
+            .get(id)
+            .and_then(|i| i.get(nth))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// A `Key: value` line at the end of the reply, such as `Reviewed-by: Name <address>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trailer {
+    pub key: String,
+    pub value: String,
+    /// What the trailer is limited to, written after a `#` as b4 expects.
+    pub comment: Option<String>,
+}
+
+impl FromStr for Trailer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(['\r', '\n']) {
+            return Err(format!("trailer {s:?} can't contain a line break"));
+        }
+
+        // A bare KEY is given with our own identity, which gets filled in later.
+        let Some((key, value)) = s.split_once('=') else {
+            let key = s.trim();
+            if key.is_empty() || key.contains(|c: char| c.is_whitespace() || c == ':') {
+                return Err(format!("trailer {s} isn't in the KEY or KEY=VALUE form"));
+            }
+
+            return Ok(Trailer {
+                key: key.to_string(),
+                value: String::new(),
+                comment: None,
+            });
+        };
+
+        let key = key.trim();
+        let value = value.trim();
+        if key.is_empty() || value.is_empty() {
+            return Err(format!("trailer {s} has an empty key or value"));
+        }
+
+        Ok(Trailer {
+            key: key.to_string(),
+            value: value.to_string(),
+            comment: None,
+        })
+    }
+}
+
+impl fmt::Display for Trailer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.value)?;
+        if let Some(comment) = &self.comment {
+            write!(f, " # {comment}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits the `# comment` off a trailer value, such as `Name <address> # for the DT bits`.
+fn split_trailer_comment(value: &str) -> (&str, Option<&str>) {
+    match value.split_once(" #") {
+        Some((value, comment)) if !comment.trim().is_empty() => {
+            (value.trim_end(), Some(comment.trim()))
+        }
+        _ => (value, None),
+    }
+}
+
+fn parse_comment(s: &str) -> Result<String, String> {
+    if s.contains(['\r', '\n']) {
+        return Err(format!("comment {s:?} can't contain a line break"));
+    }
+
+    let comment = s.trim().trim_start_matches('#').trim_start();
+    if comment.is_empty() {
+        return Err(String::from("the comment is empty"));
+    }
+
+    Ok(comment.to_string())
+}
+
+/// How the keys of the trailers we add are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum TrailerStyle {
+    /// The kernel style, e.g. `Acked-by`
+    #[default]
+    Canonical,
+
+    /// All lowercase, e.g. `acked-by`
+    Lowercase,
+
+    /// As given on the command line
+    Verbatim,
+}
+
+impl TrailerStyle {
+    fn apply(self, key: &str) -> String {
+        match self {
+            TrailerStyle::Canonical => {
+                let key = key.to_lowercase();
+                let mut chars = key.chars();
+
+                chars
+                    .next()
+                    .map(|c| c.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+            TrailerStyle::Lowercase => key.to_lowercase(),
+            TrailerStyle::Verbatim => key.to_string(),
+        }
+    }
+}
+
+impl FromStr for TrailerStyle {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "canonical" => Ok(TrailerStyle::Canonical),
+            "lowercase" => Ok(TrailerStyle::Lowercase),
+            "verbatim" => Ok(TrailerStyle::Verbatim),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Tags given to some patches of the series only, in the reply to the cover letter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PatchTags {
+    /// The ranges of patches, both ends included.
+    patches: Vec<(usize, usize)>,
+    keys: Vec<&'static str>,
+}
+
+impl PatchTags {
+    fn last(&self) -> usize {
+        self.patches
+            .iter()
+            .map(|(_, last)| *last)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for PatchTags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges: Vec<_> = self
+            .patches
+            .iter()
+            .map(|&(first, last)| {
+                if first == last {
+                    first.to_string()
+                } else {
+                    format!("{first}-{last}")
+                }
+            })
+            .collect();
+
+        let single = matches!(self.patches[..], [(first, last)] if first == last);
+        write!(
+            f,
+            "{} {}",
+            if single { "patch" } else { "patches" },
+            ranges.join(", ")
+        )
+    }
+}
+
+fn parse_patch_tags(s: &str) -> Result<PatchTags, String> {
+    let invalid = || format!("{s} isn't in the PATCHES:TAGS form, such as 1-3:reviewed");
+    let (patches, tags) = s.split_once(':').ok_or_else(invalid)?;
+
+    let patches = patches
+        .split(',')
+        .map(|range| {
+            let (first, last) = range.split_once('-').unwrap_or((range, range));
+            let first = usize::from_str(first.trim()).map_err(|_| invalid())?;
+            let last = usize::from_str(last.trim()).map_err(|_| invalid())?;
+            if first == 0 || last < first {
+                return Err(format!("invalid range of patches: {range}"));
+            }
+
+            Ok((first, last))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let keys = tags
+        .split(',')
+        .map(|tag| match tag.trim().to_lowercase().as_str() {
+            "acked" | "acked-by" => Ok("Acked-by"),
+            "reviewed" | "reviewed-by" => Ok("Reviewed-by"),
+            "tested" | "tested-by" => Ok("Tested-by"),
+            "signed-off" | "signed-off-by" => Ok("Signed-off-by"),
+            _ => Err(format!(
+                "unknown tag {tag}, expected acked, reviewed, tested or signed-off"
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(PatchTags { patches, keys })
+}
+
+fn parse_charset(s: &str) -> Result<String, String> {
+    if charset_decoder(s.as_bytes()).is_none() {
+        return Err(format!("unsupported charset: {s}"));
+    }
+
+    Ok(s.to_string())
+}
+
+fn parse_reply_count(s: &str) -> Result<usize, String> {
+    match usize::from_str(s) {
+        Ok(0) => Err(String::from(
+            "at least one line per paragraph must be quoted",
+        )),
+        Ok(count) => Ok(count),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_date(s: &str) -> Result<DateTime, String> {
+    DateTime::parse_rfc822(s).ok_or_else(|| format!("invalid RFC 2822 date: {s}"))
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Timezone {
+    before_gmt: bool,
+    hour: u8,
+    minute: u8,
+}
+
+impl Timezone {
+    /// Returns the timezone `offset` seconds away from GMT.
+    fn from_offset(offset: i32) -> Self {
+        let minutes = offset.unsigned_abs() / 60;
+
+        Timezone {
+            before_gmt: offset < 0,
+            hour: u8::try_from(minutes / 60).unwrap_or_default(),
+            minute: u8::try_from(minutes % 60).unwrap_or_default(),
+        }
+    }
+
+    /// Returns the timezone of `date`.
+    fn from_date(date: &DateTime) -> Self {
+        Timezone {
+            before_gmt: date.tz_before_gmt,
+            hour: date.tz_hour,
+            minute: date.tz_minute,
+        }
+    }
+
+    fn offset(self) -> i64 {
+        let offset = i64::from(self.hour) * 3600 + i64::from(self.minute) * 60;
+
+        if self.before_gmt {
+            -offset
+        } else {
+            offset
+        }
+    }
+
+    fn date(self, timestamp: i64) -> DateTime {
+        let mut date = DateTime::from_timestamp(timestamp + self.offset());
+
+        date.tz_before_gmt = self.before_gmt;
+        date.tz_hour = self.hour;
+        date.tz_minute = self.minute;
+
+        date
+    }
+}
+
+impl FromStr for Timezone {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if ["UTC", "GMT", "Z"]
+            .iter()
+            .any(|z| s.eq_ignore_ascii_case(z))
+        {
+            return Ok(Timezone {
+                before_gmt: false,
+                hour: 0,
+                minute: 0,
+            });
+        }
+
+        let (before_gmt, offset) = if let Some(o) = s.strip_prefix('-') {
+            (true, o)
+        } else if let Some(o) = s.strip_prefix('+') {
+            (false, o)
+        } else {
+            return Err(());
+        };
+
+        let offset = offset.replace(':', "");
+        if offset.len() != 4 || !offset.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(());
+        }
+
+        let hour = offset[..2].parse().map_err(|_| ())?;
+        let minute = offset[2..].parse().map_err(|_| ())?;
+        if hour > 23 || minute > 59 {
+            return Err(());
+        }
+
+        Ok(Timezone {
+            before_gmt,
+            hour,
+            minute,
+        })
+    }
+}
+
+/// Decodes a raw value of the git configuration. git doesn't care about the encoding of its
+/// configuration, and the ones predating UTF-8 are usually in Latin-1.
+fn decode_config_value(value: &[u8]) -> String {
+    match std::str::from_utf8(value) {
+        Ok(v) => v.to_string(),
+        Err(_) => value.iter().copied().map(char::from).collect(),
+    }
+}
+
+/// Logs the value of `key` we read, with `-vv`. The passwords and tokens only show as set.
+fn log_config<T: fmt::Debug>(key: &str, value: Option<&T>) {
+    let lowercase = key.to_ascii_lowercase();
+    match value {
+        Some(_) if lowercase.ends_with("pass") || lowercase.ends_with("token") => {
+            debug!("{key} = <masked>");
+        }
+        Some(v) => debug!("{key} = {v:?}"),
+        None => trace!("{key} is not set"),
+    }
+}
+
+fn get_config_string(cfg: &GitFile<'_>, key: &str) -> Option<String> {
+    let value = cfg.string_by_key(key).map(|v| decode_config_value(&v));
+    log_config(key, value.as_ref());
+
+    value
+}
+
+fn get_config_strings(cfg: &GitFile<'_>, key: &str) -> Vec<String> {
+    let values: Vec<_> = cfg
+        .strings_by_key(key)
+        .unwrap_or_default()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    log_config(key, Some(&values).filter(|v| !v.is_empty()));
+
+    values
+}
+
+fn get_config_usize(cfg: &GitFile<'_>, key: &'static str) -> Result<Option<usize>, Error> {
+    let value = cfg
+        .integer_by_key(key)
+        .transpose()
+        .map_err(|e| Error::InvalidConfig(key, e.input.to_string()))?
+        .map(|v| usize::try_from(v).map_err(|_| Error::InvalidConfig(key, v.to_string())))
+        .transpose()?;
+    log_config(key, value.as_ref());
+
+    Ok(value)
+}
+
+fn get_config_bool(cfg: &GitFile<'_>, key: &'static str) -> Result<Option<bool>, Error> {
+    let value = cfg
+        .boolean_by_key(key)
+        .transpose()
+        .map_err(|e| Error::InvalidConfig(key, e.input.to_string()))?;
+    log_config(key, value.as_ref());
+
+    Ok(value)
+}
+
+/// Makes the keys of the `[sendemail "<identity>"]` section override the plain `sendemail.*` ones,
+/// just like `git send-email --identity` does.
+fn apply_identity(cfg: &mut GitFile<'static>, identity: Option<&str>) -> Result<(), Error> {
+    let Some(identity) = identity
+        .map(String::from)
+        .or_else(|| get_config_string(cfg, "sendemail.identity"))
+    else {
+        return Ok(());
+    };
+    info!("using the sendemail.{identity} identity");
+
+    let mut values = Vec::new();
+    for section in cfg.sections_by_name("sendemail").into_iter().flatten() {
+        if section.header().subsection_name() != Some(identity.as_str().into()) {
+            continue;
+        }
+
+        for key in section.keys() {
+            for value in section.values(key.as_ref()) {
+                values.push((key.clone(), value.into_owned()));
+            }
+        }
+    }
+
+    if values.is_empty() {
+        return Ok(());
+    }
+
+    // Later values win, so a section appended at the end overrides everything else.
+    let mut section = cfg
+        .new_section("sendemail", None)
+        .map_err(|e| Error::InvalidConfig("sendemail.identity", e.to_string()))?;
+    for (key, value) in &values {
+        section.push(key.clone(), Some(value.as_ref()));
+    }
+
+    Ok(())
+}
+
+/// The keys of an `[acker "<profile>"]` section that override a git one rather than the
+/// `acker.*` key of the same name.
+const PROFILE_KEYS: [(&str, &str, &str); 6] = [
+    ("identity", "sendemail", "identity"),
+    ("smtp", "sendemail", "smtpServer"),
+    ("smtpServerPort", "sendemail", "smtpServerPort"),
+    ("smtpUser", "sendemail", "smtpUser"),
+    ("smtpPass", "sendemail", "smtpPass"),
+    ("smtpEncryption", "sendemail", "smtpEncryption"),
+];
+
+/// Returns the keys the `[acker "<profile>"]` section overrides, as section, key and value, with
+/// its `from` address split into `user.name` and `user.email`.
+fn get_profile(
+    cfg: &GitFile<'_>,
+    profile: Option<&str>,
+) -> Result<Vec<(&'static str, String, String)>, Error> {
+    let Some(profile) = profile
+        .map(String::from)
+        .or_else(|| get_config_string(cfg, "acker.profile"))
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut found = false;
+    let mut values = Vec::new();
+    for section in cfg.sections_by_name("acker").into_iter().flatten() {
+        if section.header().subsection_name() != Some(profile.as_str().into()) {
+            continue;
+        }
+        found = true;
+
+        for key in section.keys() {
+            for value in section.values(key.as_ref()) {
+                let value = decode_config_value(&value);
+
+                if key.eq_ignore_ascii_case(b"from") {
+                    let from = Mailbox::from_str(value.trim()).map_err(|_| {
+                        Error::InvalidAddress("acker.<profile>.from", value.clone())
+                    })?;
+                    if let Some(name) = from.name {
+                        values.push(("user", String::from("name"), name));
+                    }
+                    values.push(("user", String::from("email"), from.email.to_string()));
+                    continue;
+                }
+
+                let (section, key) = PROFILE_KEYS
+                    .iter()
+                    .find(|(k, _, _)| key.eq_ignore_ascii_case(k.as_bytes()))
+                    .map_or(("acker", key.to_string()), |(_, s, k)| {
+                        (*s, (*k).to_string())
+                    });
+                values.push((section, key, value));
+            }
+        }
+    }
+
+    if !found {
+        return Err(Error::InvalidConfig(
+            "acker.profile",
+            format!("{profile}, there's no [acker \"{profile}\"] section"),
+        ));
+    }
+
+    Ok(values)
+}
+
+/// Makes the keys of the profile override the ones of the rest of the configuration.
+fn apply_profile(
+    cfg: &mut GitFile<'static>,
+    values: &[(&'static str, String, String)],
+) -> Result<(), Error> {
+    for name in ["user", "sendemail", "acker"] {
+        let values: Vec<_> = values.iter().filter(|(s, _, _)| *s == name).collect();
+        if values.is_empty() {
+            continue;
+        }
+
+        // Later values win, so a section appended at the end overrides everything else.
+        let mut section = cfg
+            .new_section(name, None)
+            .map_err(|e| Error::InvalidConfig("acker.profile", e.to_string()))?;
+        for (_, key, value) in values {
+            let key = key
+                .clone()
+                .try_into()
+                .map_err(|_| Error::InvalidConfig("acker.profile", key.clone()))?;
+            section.push(key, Some(value.as_str().into()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Makes sure a value we're about to put in a header can't be used to inject other headers.
+fn check_header_value(field: &'static str, value: &str) -> Result<(), Error> {
+    if value.contains(['\r', '\n']) {
+        return Err(Error::InvalidHeaderValue(field, value.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Removes any line break from a value coming from the original message, so that it can't be
+/// used to inject other headers in our reply.
+fn sanitize_header_value(value: &str) -> String {
+    value
+        .split(['\r', '\n'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Unfolds a header value that may have been folded, or mangled, over several lines and collapses
+/// its whitespace, so that it reads as a single line.
+fn unfold_header_value(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn get_user_name(cfg: &GitFile<'_>) -> Result<Option<String>, Error> {
+    cfg.string_by_key("user.name")
+        .map(|n| {
+            let n = decode_config_value(&n);
+            check_header_value("user.name", &n)?;
+
+            Ok(n)
+        })
+        .transpose()
+}
+
+fn get_user_addr(cfg: &GitFile<'_>) -> Result<Option<Address>, Error> {
+    cfg.string_by_key("user.email")
+        .map(|m| {
+            let m = std::str::from_utf8(m.as_ref())
+                .map_err(|_| Error::InvalidConfig("user.email", m.to_string()))?;
+
+            Address::from_str(m).map_err(|_| Error::InvalidAddress("user.email", m.to_string()))
+        })
+        .transpose()
+}
+
+fn get_user_mail(cfg: &GitFile<'_>) -> Result<Mailbox, Error> {
+    get_user_mail_with_env(cfg, |var| std::env::var(var).ok())
+}
+
+/// Returns the From and Sender of the reply. When sending on behalf of a shared address, given
+/// with `--from` or `acker.from`, `user` is the Sender, so that the reply stays attributable.
+fn get_reply_from(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    user: Mailbox,
+) -> Result<(Mailbox, Option<Mailbox>), Error> {
+    let from = match &args.from {
+        Some(from) => Some(from.clone()),
+        None => get_config_string(cfg, "acker.from")
+            .map(|f| {
+                Mailbox::from_str(f.trim()).map_err(|_| Error::InvalidAddress("acker.from", f))
+            })
+            .transpose()?,
+    };
+
+    Ok(match from {
+        Some(from) if from.email != user.email => (from, Some(user)),
+        _ => (user, None),
+    })
+}
+
+/// Returns our identity, looking for the address in the same places than git send-email before
+/// falling back to `acker.defaultFrom`.
+fn get_user_mail_with_env(
+    cfg: &GitFile<'_>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Mailbox, Error> {
+    let name = get_user_name(cfg)?;
+    if let Some(mail) = get_user_addr(cfg)? {
+        return Ok(Mailbox::new(name, mail));
+    }
+
+    for (source, value) in [
+        ("sendemail.from", get_config_string(cfg, "sendemail.from")),
+        ("GIT_AUTHOR_EMAIL", env("GIT_AUTHOR_EMAIL")),
+        ("EMAIL", env("EMAIL")),
+        (
+            "acker.defaultFrom",
+            get_config_string(cfg, "acker.defaultFrom"),
+        ),
+    ] {
+        let Some(value) = value.filter(|v| !v.trim().is_empty()) else {
+            continue;
+        };
+
+        let mailbox = Mailbox::from_str(value.trim())
+            .map_err(|_| Error::InvalidAddress(source, value.clone()))?;
+
+        return Ok(Mailbox::new(mailbox.name.or(name), mailbox.email));
+    }
+
+    Err(Error::NoSender)
+}
+
+/// Returns the date to put in the reply, if it shouldn't be the current time in UTC.
+fn get_reply_date(cfg: &GitFile<'_>, args: &Args) -> Result<Option<DateTime>, Error> {
+    let tz = get_config_string(cfg, "acker.replyTimezone")
+        .map(|tz| {
+            Timezone::from_str(&tz).map_err(|()| Error::InvalidConfig("acker.replyTimezone", tz))
+        })
+        .transpose()?;
+
+    Ok(match (&args.date, tz) {
+        (Some(date), None) => Some(*date),
+        (Some(date), Some(tz)) => Some(tz.date(date.to_timestamp())),
+        (None, Some(tz)) => {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+
+            Some(tz.date(i64::try_from(now).unwrap_or_default()))
+        }
+        (None, None) => None,
+    })
+}
+
+/// Warns if the original message is older than `acker.staleAfterDays`, since acking a long
+/// abandoned patch is usually a mistake.
+fn check_stale(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    msg: &mail_parser::Message<'_>,
+) -> Result<(), Error> {
+    let days = get_config_usize(cfg, "acker.staleAfterDays")?.unwrap_or(DEFAULT_STALE_AFTER_DAYS);
+    if days == 0 || args.force {
+        return Ok(());
+    }
+
+    let Some(date) = msg.date() else {
+        return Ok(());
+    };
+
+    let now = args.date.map_or_else(
+        || {
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or_default())
+        },
+        |d| d.to_timestamp(),
+    );
+
+    let age = (now - date.to_timestamp()) / (24 * 60 * 60);
+    if usize::try_from(age).is_ok_and(|age| age > days) {
+        eprintln!(
+            "acker: warning: the original message is {age} days old, more than the {days} days of acker.staleAfterDays"
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the address of the person a trailer is about, if it's about someone.
+fn get_trailer_address(value: &str) -> Option<String> {
+    Mailbox::from_str(split_trailer_comment(value.trim()).0)
+        .ok()
+        .map(|m| m.email.to_string())
+}
+
+/// Returns the trailers of the original message that are about someone, as their key and the
+/// address of that person.
+fn get_original_tags(body: &str) -> Vec<(&str, String)> {
+    get_original_trailers(body)
+        .into_iter()
+        .filter_map(|l| l.split_once(':'))
+        .filter_map(|(key, value)| Some((key.trim(), get_trailer_address(value)?)))
+        .collect()
+}
+
+/// Leaves out of `trailers` the ones the original message already carries, such as the
+/// Reviewed-by of a previous version picked up in a resend, unless `--force` is given.
+fn drop_original_tags(
+    args: &Args,
+    msg: &mail_parser::Message<'_>,
+    trailers: TrailerSet,
+) -> Result<TrailerSet, Error> {
+    if args.force {
+        return Ok(trailers);
+    }
+
+    let body = get_mail_body(args, msg)?;
+    let original = get_original_tags(&body);
+    let is_original = |trailer: &Trailer| {
+        get_trailer_address(&trailer.value).is_some_and(|address| {
+            original.iter().any(|(k, a)| {
+                k.eq_ignore_ascii_case(&trailer.key) && a.eq_ignore_ascii_case(&address)
+            })
+        })
+    };
+
+    let (dropped, kept): (Vec<_>, Vec<_>) = trailers.0.into_iter().partition(is_original);
+    // With nothing left to give, check_already_acked tells the user.
+    if kept.is_empty() {
+        return Ok(dropped.into_iter().collect());
+    }
+
+    for trailer in &dropped {
+        eprintln!("acker: warning: the patch already carries {trailer}, leaving it out");
+    }
+
+    Ok(kept.into_iter().collect())
+}
+
+/// Returns the command to test the patches with before giving them a Tested-by, if any.
+fn get_test_cmd(cfg: &GitFile<'_>, args: &Args) -> Option<String> {
+    if !args.tested {
+        return None;
+    }
+
+    args.test_cmd
+        .clone()
+        .or_else(|| get_config_string(cfg, "acker.testCmd"))
+        .filter(|c| !c.trim().is_empty())
+}
+
+/// Makes sure the patches apply to the work tree, each after the ones before it in the series,
+/// before sending any reply.
+fn check_apply(args: &Args, messages: &[mail_parser::Message<'_>]) -> Result<(), Error> {
+    let mut check = patch::ApplyCheck::default();
+
+    for msg in messages {
+        let body = get_mail_body(args, msg)?;
+        if !patch::has_diff(&body) || check.check(&body)? {
+            continue;
+        }
+
+        let err = Error::PatchDoesntApply(msg.subject().unwrap_or_default().to_string());
+        if !(args.dry || args.force) {
+            return Err(err);
+        }
+
+        eprintln!("acker: warning: {err}");
+    }
+
+    Ok(())
+}
+
+/// Tests `msg` in the work tree before giving it a Tested-by.
+fn test_patch(
+    args: &Args,
+    worktree: &mut patch::Worktree,
+    msg: &mail_parser::Message<'_>,
+) -> Result<(), Error> {
+    let body = get_mail_body(args, msg)?;
+    let subject = msg.subject().unwrap_or_default();
+
+    // Cover letters have nothing to apply, the patches of the series get tested on their own.
+    if !patch::has_diff(&body) {
+        eprintln!("acker: warning: \"{subject}\" has no diff to test");
+        return Ok(());
+    }
+
+    worktree.test(subject, &body)
+}
+
+/// Warns about the patches that don't look ready for a tag, such as the ones without a
+/// Signed-off-by, and refuses to send with `--strict`.
+fn check_patch(args: &Args, msg: &mail_parser::Message<'_>) -> Result<(), Error> {
+    if args.cover_letter || series::is_cover_letter(msg) {
+        return Ok(());
+    }
+
+    let body = get_mail_body(args, msg)?;
+    let author = match get_patch_author(&body)? {
+        Some((author, _)) => author,
+        None => get_mail_from(msg)?,
+    };
+
+    let problems = patch::check(msg.subject().unwrap_or_default(), &body, &author);
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    if args.strict && !args.dry && !args.force {
+        return Err(Error::InvalidPatch(problems.join(", ")));
+    }
+
+    for problem in problems {
+        eprintln!("acker: warning: {problem}");
+    }
+
+    Ok(())
+}
+
+/// Makes sure we don't give the same trailer twice, either because the original message already
+/// carries it (a resend picking up tags, for example), or because we sent it on a previous run.
+fn check_already_acked(
+    args: &Args,
+    log: &state::SentLog,
+    msg: &mail_parser::Message<'_>,
+    trailers: &TrailerSet,
+) -> Result<(), Error> {
+    if args.force {
+        return Ok(());
+    }
+
+    let Some(id) = msg.message_id() else {
+        return Ok(());
+    };
+
+    let body = get_mail_body(args, msg)?;
+    let original = get_original_tags(&body);
+
+    for trailer in trailers {
+        let Some(address) = get_trailer_address(&trailer.value) else {
+            continue;
+        };
+
+        let in_body = original
+            .iter()
+            .any(|(k, a)| k.eq_ignore_ascii_case(&trailer.key) && a.eq_ignore_ascii_case(&address));
+        if !in_body && !log.contains(id, &trailer.key, &address) {
+            continue;
+        }
+
+        let err = Error::AlreadyAcked(trailer.to_string(), id.to_string());
+        if !args.dry {
+            return Err(err);
+        }
+
+        eprintln!("acker: warning: {err}");
+    }
+
+    Ok(())
+}
+
+/// Returns who we send `draft` as: the `Sender`, or the `From` without one.
+fn get_draft_user(draft: &Draft) -> &Mailbox {
+    draft.sender.as_ref().unwrap_or(&draft.from)
+}
+
+/// Warns about the `duplicates` of the reply to `msg`. Returns whether to skip it, with
+/// `--skip-if-tagged`.
+fn report_duplicates(args: &Args, msg: &mail_parser::Message<'_>, duplicates: Vec<String>) -> bool {
+    if duplicates.is_empty() {
+        return false;
+    }
+
+    let subject = unfold_header_value(msg.subject().unwrap_or_default());
+    if args.skip_if_tagged {
+        eprintln!("acker: skipping \"{subject}\": {}", duplicates.join(", "));
+        return true;
+    }
+
+    for duplicate in duplicates {
+        eprintln!("acker: warning: {duplicate}");
+    }
+
+    false
+}
+
+/// Warns when the reply giving `trailers` would be redundant: we already replied to `msg`, or
+/// someone in its `thread` already gave the same tag. Returns whether to skip it, with
+/// `--skip-if-tagged`.
+fn check_duplicates(
+    args: &Args,
+    log: &state::SentLog,
+    thread: Option<&thread::Thread>,
+    msg: &mail_parser::Message<'_>,
+    user: &Mailbox,
+    trailers: &TrailerSet,
+) -> Result<bool, Error> {
+    let mut duplicates = match thread {
+        Some(thread) => thread.find_duplicates(args, msg, user, trailers)?,
+        None => Vec::new(),
+    };
+
+    let replied = msg.message_id().is_some_and(|id| !log.given(id).is_empty());
+    if replied
+        && !duplicates
+            .iter()
+            .any(|d| d.starts_with("we already replied"))
+    {
+        duplicates.push(String::from("we already replied to it"));
+    }
+
+    Ok(report_duplicates(args, msg, duplicates))
+}
+
+/// Returns whether the session already has a reply to `msg`, which `--resume` skips.
+fn check_resumed(session: &session::Session, msg: &mail_parser::Message<'_>) -> bool {
+    let Some(trailers) = msg.message_id().and_then(|id| session.replied(id)) else {
+        return false;
+    };
+
+    let subject = unfold_header_value(msg.subject().unwrap_or_default());
+    if trailers.is_empty() {
+        eprintln!("acker: skipping \"{subject}\": already replied to");
+    } else {
+        eprintln!(
+            "acker: skipping \"{subject}\": already replied to with {}",
+            trailers.join(", ")
+        );
+    }
+
+    true
+}
+
+/// Warns when we nacked a previous version of `msg`, which might be sent again without the
+/// problems being addressed.
+fn check_nacked(args: &Args, log: &state::SentLog, msg: &mail_parser::Message<'_>) {
+    if args.nack {
+        return;
+    }
+
+    let subject = unfold_header_value(msg.subject().unwrap_or_default());
+    let title = applied::get_title(&subject);
+    if !title.is_empty() && log.nacked(title) {
+        eprintln!("acker: warning: we nacked a previous version of \"{title}\"");
+    }
+}
+
+/// Records the trailers we just sent in reply to `msg`.
+fn record_sent_trailers(
+    log: &mut state::SentLog,
+    msg: &mail_parser::Message<'_>,
+    trailers: &TrailerSet,
+) {
+    let Some(id) = msg.message_id() else {
+        return;
+    };
+
+    // Patches usually come through a list, the others go by the domain of their author.
+    let list = match get_message_list(msg) {
+        Some(list) => list.to_string(),
+        None => get_mail_from(msg)
+            .map(|m| m.email.domain().to_string())
+            .unwrap_or_default(),
+    };
+    let subject = unfold_header_value(msg.subject().unwrap_or_default());
+    let title = applied::get_title(&subject);
+
+    for trailer in trailers {
+        let Some(address) = get_trailer_address(&trailer.value) else {
+            continue;
+        };
+
+        if let Err(e) = log.record(id, &trailer.key, &address, &list, title) {
+            eprintln!("acker: warning: {e}");
+            return;
+        }
+    }
+}
+
+/// Everything to do once a reply is sent, to keep track of it. The reply is gone already, so
+/// any error is only worth a warning.
+#[derive(Debug)]
+struct Bookkeeping {
+    log: state::SentLog,
+    patchwork: Option<patchwork::Patchwork>,
+    notmuch: Option<notmuch::Notmuch>,
+    fcc: Option<PathBuf>,
+    imap: Option<imap::Imap>,
+    post_send_hook: Option<String>,
+    session: session::Session,
+}
+
+impl Bookkeeping {
+    fn new(cfg: &GitFile<'_>, args: &Args, session: session::Session) -> Result<Self, Error> {
+        Ok(Bookkeeping {
+            log: state::SentLog::load()?,
+            // A nack doesn't move the patch forward, and isn't a successful check either.
+            patchwork: if args.dry || args.nack {
+                None
+            } else {
+                patchwork::Patchwork::from_config(cfg)?
+            },
+            notmuch: notmuch::Notmuch::from_config(cfg)?,
+            fcc: get_fcc_path(cfg, args)?,
+            imap: imap::Imap::from_config(cfg)?,
+            post_send_hook: get_config_string(cfg, "acker.postSendHook"),
+            session,
+        })
+    }
+
+    fn record_session(&mut self, msg: &mail_parser::Message<'_>, trailers: &TrailerSet) {
+        if let Some(id) = msg.message_id() {
+            if let Err(e) = self.session.record(id, trailers) {
+                eprintln!("acker: warning: {e}");
+            }
+        }
+    }
+
+    /// Only a sent reply can be seen by others, but we won't give its trailers twice anyway.
+    fn queued(&mut self, msg: &mail_parser::Message<'_>, trailers: &TrailerSet) {
+        record_sent_trailers(&mut self.log, msg, trailers);
+        self.record_session(msg, trailers);
+    }
+
+    fn sent(&mut self, msg: &mail_parser::Message<'_>, reply: &Reply) {
+        let (eml, trailers) = (&reply.message, &reply.trailers);
+        record_sent_trailers(&mut self.log, msg, trailers);
+        self.record_session(msg, trailers);
+        update_patchwork(self.patchwork.as_ref(), msg, trailers);
+
+        if let Some(path) = &self.fcc {
+            if let Err(e) = fcc::save(path, eml) {
+                eprintln!("acker: warning: {e}");
+            }
+        }
+
+        if let Some(imap) = &self.imap {
+            if let Err(e) = imap.append(eml) {
+                eprintln!("acker: warning: {e}");
+            }
+        }
+
+        if let Some(notmuch) = &self.notmuch {
+            if let Err(e) = notmuch.sent(msg.message_id(), eml) {
+                eprintln!("acker: warning: {e}");
+            }
+        }
+
+        if let Some(hook) = &self.post_send_hook {
+            if let Err(e) = hook::run(hook, reply, msg.message_id()) {
+                eprintln!("acker: warning: {e}");
+            }
+        }
+    }
+}
+
+/// Returns where to save a copy of the sent replies, if anywhere.
+fn get_fcc_path(cfg: &GitFile<'_>, args: &Args) -> Result<Option<PathBuf>, Error> {
+    if let Some(path) = &args.fcc {
+        return Ok(Some(path.clone()));
+    }
+
+    cfg.path_by_key("acker.fcc")
+        .map(|p| {
+            p.interpolate(PathContext {
+                home_dir: home::home_dir().as_deref(),
+                ..Default::default()
+            })
+            .map(std::borrow::Cow::into_owned)
+            .map_err(|e| Error::InvalidConfig("acker.fcc", e.to_string()))
+        })
+        .transpose()
+}
+
+/// Updates the patch we just replied to in Patchwork.
+fn update_patchwork(
+    patchwork: Option<&patchwork::Patchwork>,
+    msg: &mail_parser::Message<'_>,
+    trailers: &TrailerSet,
+) {
+    let (Some(patchwork), Some(id)) = (patchwork, msg.message_id()) else {
+        return;
+    };
+
+    if let Err(e) = patchwork.update(id, trailers) {
+        eprintln!("acker: warning: {e}");
+    }
+}
+
+/// Makes sure the domain we send `draft` from matches the one of the SMTP user, if any.
+fn check_from_domain(cfg: &GitFile<'_>, args: &Args, draft: &Draft) -> Result<(), Error> {
+    if !get_config_bool(cfg, "acker.checkFromDomain")?.unwrap_or(false) {
+        return Ok(());
+    }
+
+    let from = &get_draft_user(draft).email;
+    let Some(user) = get_config_string(cfg, "sendemail.smtpuser") else {
+        return Ok(());
+    };
+
+    let Some((_, user_domain)) = user.rsplit_once('@') else {
+        return Ok(());
+    };
+
+    if from.domain().eq_ignore_ascii_case(user_domain) {
+        return Ok(());
+    }
+
+    let err = Error::FromDomainMismatch(from.to_string(), user.clone());
+    if args.dry || args.force {
+        eprintln!("acker: warning: {err}");
+        return Ok(());
+    }
+
+    Err(err)
+}
+
+/// The transports `--transport` can pick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum TransportKind {
+    /// The program of sendemail.sendmailcmd or sendemail.smtpserver, or else sendmail
+    Sendmail,
+    /// The server of sendemail.smtpserver
+    Smtp,
+    /// The standard output, for another program to send the replies
+    Stdout,
+}
+
+/// The ways we can hand the reply over.
+#[derive(Debug)]
+pub enum MailTransport {
+    Sendmail(SendmailTransport),
+    Smtp(SmtpRelay),
+    /// Writes the reply as an `.eml` file in a directory, for tests and other mailers.
+    File(FileTransport),
+    /// Writes the reply on the standard output.
+    Stdout,
+    /// Keeps the reply in memory, for tests to look at what would have been sent.
+    Capture(Capture),
+}
+
+/// The messages sent through a [`MailTransport::Capture`]. Its clones share them, so that one
+/// can be handed over to [`run_with`] and the other looked at afterwards.
+#[derive(Clone, Debug, Default)]
+pub struct Capture(Arc<Mutex<Vec<CapturedEmail>>>);
+
+/// A message sent through a [`MailTransport::Capture`], with its envelope.
+type CapturedEmail = (Envelope, Vec<u8>);
+
+impl Capture {
+    pub(crate) fn push(&self, envelope: &Envelope, email: &[u8]) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((envelope.clone(), email.to_vec()));
+    }
+
+    /// Returns the envelopes of the messages sent so far, in the order they were sent.
+    #[must_use]
+    pub fn envelopes(&self) -> Vec<Envelope> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(envelope, _)| envelope.clone())
+            .collect()
+    }
+
+    /// Returns the messages sent so far, as they went over the wire, in the order they were
+    /// sent.
+    #[must_use]
+    pub fn messages(&self) -> Vec<Vec<u8>> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(_, email)| email.clone())
+            .collect()
+    }
+}
+
+impl MailTransport {
+    /// Returns the transport the git configuration asks for, in the same order than git
+    /// send-email, after `acker.fileTransport`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transport configuration is invalid or incomplete, or if there's
+    /// no way to send the replies.
+    pub fn from_config(cfg: &GitFile<'_>) -> Result<Self, Error> {
+        get_mail_transport(cfg, None)
+    }
+
+    /// Returns the transport of `kind`, as configured in `cfg`, or the one the configuration
+    /// asks for if `kind` is `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration of the transport is invalid or incomplete.
+    pub fn with_kind(cfg: &GitFile<'_>, kind: Option<TransportKind>) -> Result<Self, Error> {
+        get_mail_transport(cfg, kind)
+    }
+
+    /// Sends `eml`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Send`] if the transport failed.
+    pub fn send(&self, eml: &Message) -> Result<(), Error> {
+        match self {
+            MailTransport::Sendmail(t) => t.send(eml).map_err(|e| Error::Send(e.to_string())),
+            MailTransport::Smtp(t) => t.send(eml).map_err(|e| Error::Send(e.to_string())),
+            MailTransport::File(t) => t
+                .send(eml)
+                .map(|_| ())
+                .map_err(|e| Error::Send(e.to_string())),
+            MailTransport::Stdout => std::io::stdout()
+                .write_all(&eml.formatted())
+                .map_err(|e| Error::Send(e.to_string())),
+            MailTransport::Capture(c) => {
+                c.push(eml.envelope(), &eml.formatted());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// An SMTP server, along with the credential the git credential helpers gave us for it, if any.
+/// lettre's async transport sends several messages at once over its pool of connections, the
+/// runtime it runs on only lives as long as the relay.
+#[derive(Debug)]
+pub struct SmtpRelay {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    runtime: tokio::runtime::Runtime,
+    credential: Option<credential::Credential>,
+}
+
+impl SmtpRelay {
+    /// Tells the credential helpers whether their credential worked, once the server told us.
+    fn report<T>(&self, result: &Result<T, lettre::transport::smtp::Error>) {
+        let Some(credential) = &self.credential else {
+            return;
+        };
+
+        match result {
+            Ok(_) => credential.approve(),
+            // 530, 534 and 535 are the ways the server refuses our authentication.
+            Err(e) if e.status().is_some_and(|c| c.to_string().starts_with("53")) => {
+                credential.reject();
+            }
+            Err(_) => {}
+        }
+    }
+
+    fn send(&self, eml: &Message) -> Result<(), lettre::transport::smtp::Error> {
+        self.block_on(self.send_raw(eml.envelope(), &eml.formatted()))
+    }
+
+    /// Sends `email` in a span of its own, that tells apart its dialogue with the server from the
+    /// ones of the messages sent at the same time, each over its own connection.
+    pub(crate) async fn send_raw(
+        &self,
+        envelope: &Envelope,
+        email: &[u8],
+    ) -> Result<(), lettre::transport::smtp::Error> {
+        static SENDS: AtomicUsize = AtomicUsize::new(0);
+
+        let id = SENDS.fetch_add(1, Ordering::Relaxed);
+        let result = self
+            .transport
+            .send_raw(envelope, email)
+            .instrument(tracing::debug_span!(target: trace::SMTP_TARGET, "smtp", id))
+            .await;
+        self.report(&result);
+        result.map(|_| ())
+    }
+
+    /// Runs `future`, sends through the relay, to completion.
+    pub(crate) fn block_on<F: std::future::Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
+
+fn build_smtp_transport(
+    runtime: &tokio::runtime::Runtime,
+    builder: AsyncSmtpTransportBuilder,
+) -> AsyncSmtpTransport<Tokio1Executor> {
+    // The pool of connections starts its cleanup task on the runtime as soon as it's built.
+    let _guard = runtime.enter();
+    builder.build()
+}
+
+fn get_smtp_transport(cfg: &GitFile<'_>, server: &str) -> Result<SmtpRelay, Error> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| Error::Send(e.to_string()))?;
+
+    let encryption = get_config_string(cfg, "sendemail.smtpencryption")
+        .unwrap_or_default()
+        .to_lowercase();
+
+    // git send-email calls "tls" what is STARTTLS, and "ssl" what is implicit TLS.
+    let mut builder = match encryption.as_str() {
+        "ssl" => AsyncSmtpTransport::<Tokio1Executor>::relay(server),
+        "tls" | "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(server),
+        "" | "none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+            server,
+        )),
+        _ => return Err(Error::InvalidConfig("sendemail.smtpencryption", encryption)),
+    }
+    .map_err(|e| Error::InvalidConfig("sendemail.smtpserver", e.to_string()))?;
+
+    let mut host = server.to_string();
+    if let Some(port) = get_config_usize(cfg, "sendemail.smtpserverport")? {
+        let port = u16::try_from(port)
+            .map_err(|_| Error::InvalidConfig("sendemail.smtpserverport", port.to_string()))?;
+
+        builder = builder.port(port);
+        host = format!("{server}:{port}");
+    }
+
+    info!(
+        "sending through the SMTP server {host}, with {} encryption",
+        match encryption.as_str() {
+            "ssl" => "TLS",
+            "tls" | "starttls" => "STARTTLS",
+            _ => "no",
+        }
+    );
+
+    // Some providers only take OAuth2 tokens, that tools like oauth2ms or mutt_oauth2 hand out.
+    if let Some(cmd) = get_config_string(cfg, "acker.oauthTokenCmd") {
+        let user = get_config_string(cfg, "sendemail.smtpuser")
+            .ok_or(Error::MissingConfig("sendemail.smtpuser"))?;
+        let token = credential::oauth_token(&cmd)?;
+        info!("logging in as {user} with the OAuth2 token of acker.oauthTokenCmd");
+
+        let builder = builder
+            .authentication(vec![Mechanism::Xoauth2])
+            .credentials(Credentials::new(user, token));
+        let transport = build_smtp_transport(&runtime, builder);
+        return Ok(SmtpRelay {
+            transport,
+            runtime,
+            credential: None,
+        });
+    }
+
+    let mut credential = None;
+    if let Some(user) = get_config_string(cfg, "sendemail.smtpuser") {
+        // Like git send-email, we only bother the credential helpers without a password.
+        let credentials = if let Some(pass) = get_config_string(cfg, "sendemail.smtppass") {
+            info!("logging in as {user} with the password of sendemail.smtppass");
+            Credentials::new(user, pass)
+        } else {
+            info!("logging in as {user} with the password of the git credential helpers");
+            let c = credential::Credential::fill("smtp", &host, &user)?
+                .ok_or(Error::MissingConfig("sendemail.smtppass"))?;
+            let credentials = Credentials::new(c.username().to_string(), c.password().to_string());
+            credential = Some(c);
+            credentials
+        };
+
+        builder = builder.credentials(credentials);
+    }
+
+    let transport = build_smtp_transport(&runtime, builder);
+    Ok(SmtpRelay {
+        transport,
+        runtime,
+        credential,
+    })
+}
+
+/// Returns the first sendmail of the `PATH`, or of the places it usually is in.
+fn find_sendmail() -> Option<PathBuf> {
+    let name = format!("sendmail{}", std::env::consts::EXE_SUFFIX);
+    std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .chain([PathBuf::from("/usr/sbin"), PathBuf::from("/usr/lib")])
+        .map(|dir| dir.join(&name))
+        .find(|path| path.is_file())
+}
+
+/// Returns the sendmail-like program the configuration asks for: the one of
+/// `sendemail.sendmailcmd`, or `sendemail.smtpserver` if it's one.
+fn get_sendmail_cmd(cfg: &GitFile<'_>) -> Result<Option<PathBuf>, Error> {
+    if let Some(p) = cfg.path_by_key("sendemail.sendmailcmd") {
+        let path = p
+            .interpolate(PathContext {
+                home_dir: home::home_dir().as_deref(),
+                ..Default::default()
+            })
+            .map_err(|e| Error::InvalidConfig("sendemail.sendmailcmd", e.to_string()))?;
+
+        info!(
+            "sending through {}, as sendemail.sendmailcmd asks",
+            path.display()
+        );
+        return Ok(Some(path.into_owned()));
+    }
+
+    // Just like git send-email, an absolute path is a sendmail-like program.
+    if let Some(server) = get_config_string(cfg, "sendemail.smtpserver") {
+        let path = Path::new(&server);
+        if path.is_absolute() && path.exists() {
+            info!("sending through {server}, sendemail.smtpserver being a program");
+            return Ok(Some(path.to_path_buf()));
+        }
+    }
+
+    Ok(None)
+}
+
+fn get_sendmail_transport(cfg: &GitFile<'_>) -> Result<Option<MailTransport>, Error> {
+    let path = if let Some(path) = get_sendmail_cmd(cfg)? {
+        path
+    } else {
+        let Some(path) = find_sendmail() else {
+            return Ok(None);
+        };
+
+        info!("sending through {}", path.display());
+        path
+    };
+
+    Ok(Some(MailTransport::Sendmail(
+        SendmailTransport::new_with_command(path.as_os_str()),
+    )))
+}
+
+fn get_mail_transport(
+    cfg: &GitFile<'_>,
+    kind: Option<TransportKind>,
+) -> Result<MailTransport, Error> {
+    match kind {
+        Some(TransportKind::Stdout) => {
+            info!("writing the replies on the standard output, as --transport asks");
+            return Ok(MailTransport::Stdout);
+        }
+        Some(TransportKind::Sendmail) => {
+            return get_sendmail_transport(cfg)?.ok_or(Error::NoTransport);
+        }
+        Some(TransportKind::Smtp) => {
+            let server = get_config_string(cfg, "sendemail.smtpserver")
+                .ok_or(Error::MissingConfig("sendemail.smtpserver"))?;
+            return Ok(MailTransport::Smtp(get_smtp_transport(cfg, &server)?));
+        }
+        None => {}
+    }
+
+    if let Some(p) = cfg.path_by_key("acker.fileTransport") {
+        let dir = p
+            .interpolate(PathContext {
+                home_dir: home::home_dir().as_deref(),
+                ..Default::default()
+            })
+            .map_err(|e| Error::InvalidConfig("acker.fileTransport", e.to_string()))?
+            .into_owned();
+
+        std::fs::create_dir_all(&dir).map_err(|e| Error::Io(dir.clone(), e))?;
+        info!(
+            "writing the replies to {}, as acker.fileTransport asks",
+            dir.display()
+        );
+        return Ok(MailTransport::File(FileTransport::new(dir)));
+    }
+
+    if let Some(path) = get_sendmail_cmd(cfg)? {
+        return Ok(MailTransport::Sendmail(
+            SendmailTransport::new_with_command(path.as_os_str()),
+        ));
+    }
+
+    if let Some(server) = get_config_string(cfg, "sendemail.smtpserver") {
+        return Ok(MailTransport::Smtp(get_smtp_transport(cfg, &server)?));
+    }
+
+    // Without anything configured, the system might still have a sendmail to hand it over to.
+    info!(
+        "looking for sendmail, since neither sendemail.smtpserver nor sendemail.sendmailcmd is set"
+    );
+    get_sendmail_transport(cfg)?.ok_or(Error::NoTransport)
+}
+
+fn mailbox_from_addr(field: &'static str, a: &mail_parser::Addr<'_>) -> Result<Mailbox, Error> {
+    let name = a.name.clone().map(String::from);
+    if let Some(n) = &name {
+        check_header_value(field, n)?;
+    }
+
+    let addr = a
+        .address
+        .as_deref()
+        .ok_or_else(|| Error::InvalidAddress(field, name.clone().unwrap_or_default()))?;
+    let addr =
+        Address::from_str(addr).map_err(|_| Error::InvalidAddress(field, addr.to_string()))?;
+
+    Ok(Mailbox::new(name, addr))
+}
+
+fn mailbox_from_address(
+    field: &'static str,
+    address: &mail_parser::Address<'_>,
+) -> Result<Vec<Mailbox>, Error> {
+    address
+        .clone()
+        .into_list()
+        .iter()
+        .map(|a| mailbox_from_addr(field, a))
+        .collect()
+}
+
+fn get_mail_from(msg: &mail_parser::Message<'_>) -> Result<Mailbox, Error> {
+    mailbox_from_address("From", msg.from().ok_or(Error::MissingHeader("From"))?)?
+        .into_iter()
+        .next()
+        .ok_or(Error::MissingHeader("From"))
+}
+
+/// Returns the body part of `msg` worth quoting: its first plain text one that isn't empty, or
+/// its first HTML one if that's all there is, like the patches that went through corporate mail.
+fn get_body_part<'a>(
+    msg: &'a mail_parser::Message<'a>,
+) -> Result<&'a mail_parser::MessagePart<'a>, Error> {
+    let non_empty =
+        |p: &&mail_parser::MessagePart<'_>| p.text_contents().is_some_and(|t| !t.trim().is_empty());
+
+    msg.text_bodies()
+        .filter(|p| matches!(p.body, mail_parser::PartType::Text(_)))
+        .find(non_empty)
+        .or_else(|| msg.html_bodies().find(non_empty))
+        .ok_or(Error::UnsupportedBody)
+}
+
+/// Returns the body of `part`, with its transfer encoding undone but still in its charset.
+fn get_raw_body(
+    msg: &mail_parser::Message<'_>,
+    part: &mail_parser::MessagePart<'_>,
+) -> Result<Vec<u8>, Error> {
+    let raw = msg
+        .raw_message()
+        .get(part.raw_body_offset()..part.raw_end_offset())
+        .ok_or(Error::UnsupportedBody)?;
+
+    match part.content_transfer_encoding() {
+        Some(e) if e.eq_ignore_ascii_case("base64") => base64_decode(raw),
+        Some(e) if e.eq_ignore_ascii_case("quoted-printable") => quoted_printable_decode(raw),
+        _ => Some(raw.to_vec()),
+    }
+    .ok_or(Error::UnsupportedBody)
+}
+
+/// Returns the charset to decode `part` with, if it isn't the one mail-parser went with.
+fn get_input_charset(
+    args: &Args,
+    msg: &mail_parser::Message<'_>,
+    part: &mail_parser::MessagePart<'_>,
+) -> Result<Option<String>, Error> {
+    if let Some(charset) = &args.input_charset {
+        return Ok(Some(charset.clone()));
+    }
+
+    let declared = part.content_type().and_then(|ct| ct.attribute("charset"));
+    if declared.is_some_and(|c| !c.eq_ignore_ascii_case("us-ascii")) {
+        return Ok(None);
+    }
+
+    // Without a charset, mail-parser assumes UTF-8, but the 8-bit text that doesn't say what it
+    // is usually comes from a mailer using the Windows flavour of Latin-1.
+    if std::str::from_utf8(&get_raw_body(msg, part)?).is_ok() {
+        return Ok(None);
+    }
+
+    Ok(Some(String::from(FALLBACK_CHARSET)))
+}
+
+fn get_mail_body<'a>(
+    args: &Args,
+    msg: &'a mail_parser::Message<'a>,
+) -> Result<Cow<'a, str>, Error> {
+    let part = get_body_part(msg)?;
+
+    let Some(charset) = get_input_charset(args, msg, part)? else {
+        return match &part.body {
+            mail_parser::PartType::Text(t) => Ok(match quote::get_flowed(part) {
+                Some(delsp) => Cow::Owned(quote::unflow(t, delsp)),
+                None => Cow::Borrowed(t),
+            }),
+            mail_parser::PartType::Html(h) => Ok(Cow::Owned(html_to_text(h))),
+            _ => Err(Error::UnsupportedBody),
+        };
+    };
+
+    // mail-parser already decoded the body using the declared charset, so we need to go back
+    // to the raw body and undo the transfer encoding ourselves.
+    let decoded = get_raw_body(msg, part)?;
+
+    let decode = charset_decoder(charset.as_bytes()).ok_or(Error::UnsupportedBody)?;
+    let text = decode(&decoded);
+    if part.is_text_html() {
+        return Ok(Cow::Owned(html_to_text(&text)));
+    }
+
+    if let Some(delsp) = quote::get_flowed(part) {
+        return Ok(Cow::Owned(quote::unflow(&text, delsp)));
+    }
+
+    Ok(Cow::Owned(text))
+}
+
+/// Returns the author found in the `From:` line git format-patch puts at the top of the body
+/// when the sender isn't the author of the patch, and the body without that header block.
+fn get_patch_author(body: &str) -> Result<Option<(Mailbox, &str)>, Error> {
+    let Some(author) = body.lines().next().and_then(|l| l.strip_prefix("From:")) else {
+        return Ok(None);
+    };
+
+    let author = author.trim();
+    let mailbox =
+        Mailbox::from_str(author).map_err(|_| Error::InvalidAddress("From", author.to_string()))?;
+
+    let rest = body
+        .split_once("\n\n")
+        .map_or("", |(_, rest)| rest)
+        .trim_start_matches('\n');
+
+    Ok(Some((mailbox, rest)))
+}
+
+fn get_mail_author(args: &Args, msg: &mail_parser::Message<'_>) -> Result<Mailbox, Error> {
+    if args.use_patch_author {
+        if let Some((author, _)) = get_patch_author(&get_mail_body(args, msg)?)? {
+            return Ok(author);
+        }
+    }
+
+    get_mail_from(msg)
+}
+
+/// Returns the recipients set by whatever prepared the original message in the header named by
+/// the `key` configuration, for `--respect-headers`.
+fn get_header_recipients(
+    cfg: &GitFile<'_>,
+    msg: &mail_parser::Message<'_>,
+    key: &'static str,
+    default: &str,
+) -> Result<Vec<Mailbox>, Error> {
+    let header = get_config_string(cfg, key).unwrap_or_else(|| default.to_string());
+    let Some(value) = msg.header_raw(header.as_str()) else {
+        return Ok(Vec::new());
+    };
+
+    let value = sanitize_header_value(value);
+    if value.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mailboxes = Mailboxes::from_str(&value).map_err(|_| Error::InvalidAddress(key, value))?;
+    Ok(mailboxes.into_iter().collect())
+}
+
+/// Returns the addresses of the Mail-Followup-To header of the original message, if any.
+fn get_followup_to(msg: &mail_parser::Message<'_>) -> Result<Option<Vec<Mailbox>>, Error> {
+    let Some(value) = msg.header_raw("Mail-Followup-To") else {
+        return Ok(None);
+    };
+
+    let value = sanitize_header_value(value);
+    let mailboxes = Mailboxes::from_str(&value)
+        .map_err(|_| Error::InvalidAddress("Mail-Followup-To", value.clone()))?;
+
+    Ok(Some(mailboxes.into_iter().collect()).filter(|m: &Vec<_>| !m.is_empty()))
+}
+
+/// Sends the reply where the original message asks it to go, like MUAs do: to everyone in its
+/// Mail-Followup-To, or else to its Reply-To, with the sender in Cc.
+fn apply_followup(
+    cfg: &GitFile<'_>,
+    msg: &mail_parser::Message<'_>,
+    recipients: &mut Recipients,
+) -> Result<(), Error> {
+    if let Some(mut followup) = get_followup_to(msg)? {
+        let user = get_user_mail(cfg)?;
+        if followup.len() > 1 {
+            followup.retain(|m| m.email != user.email);
+        }
+
+        // The Mail-Followup-To is the whole list of recipients, only our own copy is left.
+        info!("replying to the Mail-Followup-To of the original message");
+        recipients.cc.retain(|m| m.email == user.email);
+        recipients.to = followup;
+        return Ok(());
+    }
+
+    let Some(reply_to) = msg.reply_to() else {
+        return Ok(());
+    };
+
+    let reply_to = mailbox_from_address("Reply-To", reply_to)?;
+    if reply_to.is_empty() {
+        return Ok(());
+    }
+
+    info!("replying to the Reply-To of the original message, with the sender in Cc");
+    let sender = std::mem::replace(&mut recipients.to, reply_to);
+    recipients.cc.extend(sender);
+    recipients
+        .cc
+        .retain(|m| recipients.to.iter().all(|t| t.email != m.email));
+
+    Ok(())
+}
+
+/// Returns the address of the mailing list the original message went through, from the headers
+/// the list manager added: `List-Post`, `X-Mailing-List` or `List-Id`.
+fn get_message_list(msg: &mail_parser::Message<'_>) -> Option<Address> {
+    let bracketed = |h: &str| {
+        let h = sanitize_header_value(h);
+        h.split_once('<')
+            .and_then(|(_, rest)| rest.split_once('>'))
+            .map(|(inner, _)| inner.trim().to_string())
+    };
+
+    let post = || {
+        let post = msg.header_raw("List-Post").and_then(bracketed)?;
+        let address = post.strip_prefix("mailto:")?;
+        Address::from_str(address.split('?').next()?).ok()
+    };
+
+    // vger only adds X-Mailing-List, with the bare address.
+    let mailing_list = || {
+        let header = msg.header_raw("X-Mailing-List")?;
+        let address = bracketed(header).unwrap_or_else(|| sanitize_header_value(header));
+        Address::from_str(address.trim()).ok()
+    };
+
+    // The List-Id of most list managers is the list address, with its @ turned into a dot.
+    let list_id = || {
+        let id = msg.header_raw("List-Id").and_then(bracketed)?;
+        let (user, domain) = id.split_once('.')?;
+        Address::from_str(&format!("{user}@{domain}")).ok()
+    };
+
+    post().or_else(mailing_list).or_else(list_id)
+}
+
+/// Returns the addresses of the mailing lists we know of: the ones in `acker.listAddresses`, and
+/// the one the original message went through.
+fn get_list_addresses(cfg: &GitFile<'_>, msg: &mail_parser::Message<'_>) -> Vec<Address> {
+    get_config_strings(cfg, "acker.listAddresses")
+        .iter()
+        .filter_map(|a| Address::from_str(a.trim()).ok())
+        .chain(get_message_list(msg))
+        .collect()
+}
+
+fn is_list_address(lists: &[Address], mailbox: &Mailbox) -> bool {
+    lists.iter().any(|l| {
+        l.user().eq_ignore_ascii_case(mailbox.email.user())
+            && l.domain().eq_ignore_ascii_case(mailbox.email.domain())
+    })
+}
+
+/// Returns whether `text` matches the shell-like `pattern`, where `*` stands for any number of
+/// characters and `?` for a single one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<_>, Vec<_>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last star eat one more character.
+                Some((star, start)) => {
+                    p = star + 1;
+                    t = start + 1;
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Returns whether `mailbox` matches one of the `patterns` of addresses to drop. A pattern
+/// without an @ is about the domain, such as `bots.example.com` or `*.example.com`.
+fn is_blocked(patterns: &[String], mailbox: &Mailbox) -> bool {
+    let address = mailbox.email.to_string().to_lowercase();
+    let domain = mailbox.email.domain().to_lowercase();
+
+    patterns.iter().any(|p| {
+        let p = p.trim().to_lowercase();
+        if p.contains('@') {
+            glob_match(&p, &address)
+        } else {
+            glob_match(&p, &domain)
+        }
+    })
+}
+
+/// The categories of Cc of `sendemail.suppresscc` that make sense for a reply.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SuppressCc {
+    /// Ourselves.
+    Myself,
+    /// The sender of the original message, when we reply to the patch author.
+    Author,
+    /// The other recipients of the original message.
+    Cc,
+    All,
+    /// The categories of git send-email that have no equivalent in a reply.
+    Other,
+}
+
+fn parse_suppress_cc(s: &str) -> Result<SuppressCc, String> {
+    match s.trim().to_lowercase().as_str() {
+        "self" => Ok(SuppressCc::Myself),
+        "author" => Ok(SuppressCc::Author),
+        "cc" => Ok(SuppressCc::Cc),
+        "all" => Ok(SuppressCc::All),
+        "bodycc" | "sob" | "misc-by" | "cccmd" | "body" => Ok(SuppressCc::Other),
+        _ => Err(format!("unknown Cc category: {s}")),
+    }
+}
+
+/// Returns the categories to leave out of the Cc list: the ones given on the command line, or
+/// else the ones of `sendemail.suppresscc`.
+fn get_suppressed_cc(cfg: &GitFile<'_>, args: &Args) -> Result<Vec<SuppressCc>, Error> {
+    let mut suppress = if args.suppress_cc.is_empty() {
+        get_config_strings(cfg, "sendemail.suppresscc")
+            .iter()
+            .map(|s| {
+                parse_suppress_cc(s)
+                    .map_err(|_| Error::InvalidConfig("sendemail.suppresscc", s.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        args.suppress_cc.clone()
+    };
+
+    if args.no_cc_self {
+        suppress.push(SuppressCc::Myself);
+    }
+
+    Ok(suppress)
+}
+
+fn get_mail_cc_list(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    msg: &mail_parser::Message<'_>,
+    author: &Mailbox,
+) -> Result<Vec<Mailbox>, Error> {
+    let suppress = get_suppressed_cc(cfg, args)?;
+    debug!("suppressed Cc categories: {suppress:?}");
+    let suppressed = |category| suppress.contains(&category) || suppress.contains(&SuppressCc::All);
+
+    let user = get_user_mail(cfg)?;
+    let mut recipient_cc_list = Vec::new();
+
+    if !suppressed(SuppressCc::Myself) {
+        recipient_cc_list.push(user.clone());
+    }
+
+    // If we reply to the patch author, the sender is no longer the recipient of the reply but
+    // still needs to be kept in the loop.
+    if !suppressed(SuppressCc::Author) {
+        recipient_cc_list.push(get_mail_from(msg)?);
+    }
+
+    if !suppressed(SuppressCc::Cc) {
+        if let Some(t) = msg.to() {
+            recipient_cc_list.append(&mut mailbox_from_address("To", t)?);
+        }
+
+        if let Some(c) = msg.cc() {
+            recipient_cc_list.append(&mut mailbox_from_address("Cc", c)?);
+        }
+
+        // When we're in Cc ourselves, the list might only show in the headers it added, but
+        // the review has to land in its archive.
+        if let Some(list) = get_message_list(msg) {
+            if !recipient_cc_list
+                .iter()
+                .any(|m| is_list_address(std::slice::from_ref(&list), m))
+            {
+                recipient_cc_list.push(Mailbox::new(None, list));
+            }
+        }
+    }
+
+    if args.respect_headers {
+        recipient_cc_list.append(&mut get_header_recipients(
+            cfg,
+            msg,
+            "acker.ccHeader",
+            DEFAULT_CC_HEADER,
+        )?);
+    }
+
+    // The people the commit message itself asks to Cc, as git send-email does.
+    if args.cc_trailers {
+        for (key, address) in get_original_tags(&get_mail_body(args, msg)?) {
+            if key.eq_ignore_ascii_case("Cc") {
+                if let Ok(address) = Address::from_str(&address) {
+                    recipient_cc_list.push(Mailbox::new(None, address));
+                }
+            }
+        }
+    }
+
+    // The submitter might have forgotten someone, or the list of a subsystem.
+    if args.auto_cc {
+        for mailbox in maintainers::get(cfg, &get_mail_body(args, msg)?)? {
+            if recipient_cc_list.iter().all(|m| m.email != mailbox.email) {
+                recipient_cc_list.push(mailbox);
+            }
+        }
+    }
+
+    recipient_cc_list.sort();
+    dedup_mailboxes(&mut recipient_cc_list, &mut Vec::new());
+
+    // CI bots, no-reply addresses and the like have nothing to do with the review.
+    let mut blocked = get_config_strings(cfg, "acker.ccBlock");
+    blocked.extend(args.drop_cc.iter().cloned());
+
+    let recipient_cc_list: Vec<_> = recipient_cc_list
+        .into_iter()
+        .filter(|u| {
+            let blocked = is_blocked(&blocked, u);
+            if blocked {
+                debug!("leaving {u} out of the Cc, as acker.ccBlock or --drop-cc asks");
+            }
+            !blocked
+        })
+        .filter(|u| !same_address(&u.email, &author.email))
+        .filter(|u| !suppressed(SuppressCc::Myself) || !same_address(&u.email, &user.email))
+        .collect();
+
+    // A huge Cc list usually means we're replying to the wrong message.
+    if let Some(max) = get_config_usize(cfg, "acker.maxCc")? {
+        if recipient_cc_list.len() > max {
+            let err = Error::TooManyCc(recipient_cc_list.len(), max);
+            if !(args.dry || args.force) {
+                return Err(err);
+            }
+
+            eprintln!("acker: warning: {err}");
+        }
+    }
+
+    Ok(recipient_cc_list)
+}
+
+fn format_address(address: &mail_parser::Address<'_>) -> String {
+    address
+        .clone()
+        .into_list()
+        .iter()
+        .map(|a| match (&a.name, &a.address) {
+            (Some(n), Some(a)) => format!("{n} <{a}>"),
+            (Some(n), None) => n.to_string(),
+            (None, Some(a)) => a.to_string(),
+            (None, None) => String::new(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn get_quoted_headers(msg: &mail_parser::Message<'_>) -> String {
+    let mut headers = String::new();
+
+    for (name, address) in [("From", msg.from()), ("To", msg.to()), ("Cc", msg.cc())] {
+        if let Some(a) = address {
+            let _ = writeln!(headers, "> {name}: {}", format_address(a));
+        }
+    }
+
+    if let Some(date) = msg.date() {
+        let _ = writeln!(headers, "> Date: {}", date.to_rfc822());
+    }
+
+    if let Some(subject) = msg.subject() {
+        let _ = writeln!(headers, "> Subject: {subject}");
+    }
+
+    headers.push_str("> \n");
+    headers
+}
+
+/// Wraps a line so that no line is longer than `width` characters, unless it's made of a single
+/// word. A width of 0 disables wrapping.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    if width == 0 || line.chars().count() <= width {
+        return vec![line.to_string()];
+    }
+
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in line.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+
+        if !current.is_empty() {
+            current.push(' ');
+        }
+
+        current.push_str(word);
+    }
+
+    lines.push(current);
+    lines
+}
+
+/// Returns the line introducing the quote, wrapped.
+fn get_attribution(
+    cfg: &GitFile<'_>,
+    msg: &mail_parser::Message<'_>,
+    author: &Mailbox,
+) -> Result<String, Error> {
+    let wrap_width = get_config_usize(cfg, "acker.wrapWidth")?.unwrap_or(DEFAULT_WRAP_WIDTH);
+    let name = author.name.clone().unwrap_or(author.email.to_string());
+
+    // Some mailers manage to send messages without a date, or with one nobody can read.
+    let attribution = match get_message_date(cfg, msg)? {
+        Some(date) => format!("On {date}, {name} wrote:"),
+        None => format!("{name} wrote:"),
+    };
+    Ok(wrap_line(&attribution, wrap_width).join("\n"))
+}
+
+/// Returns the date of `msg` as the reply shows it, in the timezone of
+/// `acker.attributionTimezone` and the strftime format of `acker.dateFmt`, if it has a valid
+/// one. By default, it's the date of the message as its sender wrote it.
+fn get_message_date(
+    cfg: &GitFile<'_>,
+    msg: &mail_parser::Message<'_>,
+) -> Result<Option<String>, Error> {
+    let Some(date) = msg.date() else {
+        return Ok(None);
+    };
+
+    let timestamp = date.to_timestamp();
+    let date = match get_config_string(cfg, "acker.attributionTimezone").as_deref() {
+        None | Some("original") => *date,
+        Some("local") => {
+            let offset = chrono::DateTime::from_timestamp(timestamp, 0)
+                .map(|d| d.with_timezone(&chrono::Local).offset().local_minus_utc())
+                .unwrap_or_default();
+
+            Timezone::from_offset(offset).date(timestamp)
+        }
+        Some(tz) => Timezone::from_str(tz)
+            .map_err(|()| Error::InvalidConfig("acker.attributionTimezone", tz.to_string()))?
+            .date(timestamp),
+    };
+
+    let Some(format) = get_config_string(cfg, "acker.dateFmt") else {
+        return Ok(Some(date.to_rfc822()));
+    };
+
+    // chrono only finds out about an invalid format when it's too late to report it.
+    let items: Vec<_> = chrono::format::StrftimeItems::new(&format).collect();
+    if items.contains(&chrono::format::Item::Error) {
+        return Err(Error::InvalidConfig("acker.dateFmt", format));
+    }
+
+    let offset = i32::try_from(Timezone::from_date(&date).offset()).unwrap_or_default();
+    let date = chrono::FixedOffset::east_opt(offset)
+        .zip(chrono::DateTime::from_timestamp(timestamp, 0))
+        .map(|(offset, d)| d.with_timezone(&offset));
+    Ok(date.map(|d| d.format_with_items(items.into_iter()).to_string()))
+}
+
+/// Returns the part of the original message we quote.
+fn get_quoted_body(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    msg: &mail_parser::Message<'_>,
+) -> Result<String, Error> {
+    let marker = get_config_string(cfg, "acker.truncationMarker")
+        .unwrap_or_else(|| String::from(DEFAULT_TRUNCATION_MARKER));
+    let quote_width = get_config_usize(cfg, "acker.quoteWidth")?.unwrap_or(DEFAULT_WRAP_WIDTH);
+    let max_quote_bytes = get_config_usize(cfg, "acker.maxQuoteBytes")?;
+
+    let body = get_mail_body(args, msg)?;
+    let mut body_text: &str = &body;
+    if args.use_patch_author {
+        if let Some((_, rest)) = get_patch_author(body_text)? {
+            body_text = rest;
+        }
+    }
+
+    let mut reply_body = String::new();
+    if args.attribution_only {
+        return Ok(reply_body);
+    }
+
+    if args.quote_headers {
+        reply_body.push_str(&get_quoted_headers(msg));
+    }
+
+    // Quoting for an inline review goes through the diff, which mustn't be wrapped.
+    let inline = args.quote_all || args.quote_lines.is_some();
+    let (max_lines, max_quote_bytes, quote_width) = if args.quote_all {
+        (None, None, 0)
+    } else if inline {
+        (args.quote_lines, max_quote_bytes, 0)
+    } else {
+        (None, max_quote_bytes, quote_width)
+    };
+
+    let mut quoted_bytes = 0;
+    let mut paragraph_lines = 0;
+    for (index, line) in body_text.lines().enumerate() {
+        // Otherwise, the commit message is all the context the reply needs.
+        if !inline && (line == "---" || patch::is_diff_start(line)) {
+            break;
+        }
+
+        // With --reply-count, the limit applies to each paragraph rather than to the whole body.
+        if let Some(count) = args.reply_count {
+            if line.trim().is_empty() {
+                paragraph_lines = 0;
+            } else {
+                paragraph_lines += 1;
+                if paragraph_lines == count + 1 {
+                    let _ = writeln!(reply_body, "> {marker}");
+                }
+                if paragraph_lines > count {
+                    continue;
+                }
+            }
+        }
+
+        quoted_bytes += line.len();
+        if (args.reply_count.is_none() && max_lines.is_some_and(|max| index >= max))
+            || max_quote_bytes.is_some_and(|max| quoted_bytes > max)
+        {
+            reply_body.push_str("> \n");
+            let _ = writeln!(reply_body, "> {marker}");
+            break;
+        }
+
+        for line in quote::quote_line(line, quote_width) {
+            let _ = writeln!(reply_body, "{line}");
+        }
+    }
+
+    if args.with_diffstat {
+        let diffstat = patch::get_diffstat(&body);
+        if !diffstat.is_empty() {
+            reply_body.push_str("> \n");
+            for line in diffstat {
+                let _ = writeln!(reply_body, "> {line}");
+            }
+        }
+    }
+
+    if args.echo_trailers {
+        let trailers = get_original_trailers(body_text);
+        if !trailers.is_empty() {
+            reply_body.push_str("> \n");
+            reply_body.push_str("> [ trailers of the original message ]\n");
+            for line in trailers {
+                let _ = writeln!(reply_body, "> {line}");
+            }
+        }
+    }
+
+    Ok(reply_body)
+}
+
+fn is_trailer_line(line: &str) -> bool {
+    line.split_once(':').is_some_and(|(key, value)| {
+        !key.is_empty()
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && !value.trim().is_empty()
+    })
+}
+
+/// Returns the lines of the trailer block of the original message, that is its last paragraph
+/// before the diff if it only contains trailers.
+fn get_original_trailers(body: &str) -> Vec<&str> {
+    let commit_message = body
+        .lines()
+        .take_while(|l| *l != "---" && !l.starts_with("diff --git "));
+
+    let mut paragraph = Vec::new();
+    for line in commit_message {
+        if line.trim().is_empty() {
+            paragraph.clear();
+            continue;
+        }
+
+        paragraph.push(line.trim_end());
+    }
+
+    // Long trailers can be folded, the continuation lines starting with a space.
+    let valid = paragraph.first().is_some_and(|l| is_trailer_line(l))
+        && paragraph
+            .iter()
+            .all(|l| is_trailer_line(l) || l.starts_with([' ', '\t']));
+    if !valid {
+        return Vec::new();
+    }
+
+    paragraph
+}
+
+/// Returns the In-Reply-To and References values to thread our reply under `msg`, with the
+/// whole chain of references so that deep threads stay together.
+fn get_threading(msg: &mail_parser::Message<'_>) -> Result<(String, String), Error> {
+    let msg_id = format!(
+        "<{}>",
+        sanitize_header_value(msg.message_id().ok_or(Error::MissingHeader("Message-ID"))?)
+    );
+
+    // Without References, the parent of the message is all we know of the thread.
+    let parents = msg
+        .references()
+        .as_text_list()
+        .or_else(|| msg.in_reply_to().as_text_list())
+        .unwrap_or_default();
+
+    let mut references: Vec<_> = parents
+        .iter()
+        .map(|r| format!("<{}>", sanitize_header_value(r)))
+        .filter(|r| *r != msg_id)
+        .collect();
+    references.push(msg_id.clone());
+
+    Ok((msg_id, references.join(" ")))
+}
+
+/// Returns a new Message-ID for our reply, in the domain of its sender rather than the one of
+/// the machine we run on.
+fn make_message_id(from: &Mailbox) -> String {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    format!(
+        "<{}.{}.{}-{}.acker@{}>",
+        now.as_secs(),
+        now.subsec_nanos(),
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed),
+        from.email.domain()
+    )
+}
+
+fn get_trailers(args: &Args, user: &Mailbox) -> TrailerSet {
+    let mut trailers = Vec::new();
+
+    for (enabled, id, key) in [
+        (args.acked, "acked", "Acked-by"),
+        (args.reviewed, "reviewed", "Reviewed-by"),
+        (args.tested, "tested", "Tested-by"),
+        (args.signed_off, "signed_off", "Signed-off-by"),
+    ] {
+        if enabled {
+            trailers.push((
+                args.trailer_index(id, 0),
+                Trailer {
+                    key: key.to_string(),
+                    value: user.to_string(),
+                    comment: None,
+                },
+            ));
+        }
+    }
+
+    // Several people can legitimately give the same kind of trailer, so we must keep all of
+    // them, in the order they were given.
+    for (nth, trailer) in args.trailers.iter().enumerate() {
+        let mut trailer = trailer.clone();
+        if trailer.value.is_empty() {
+            trailer.value = user.to_string();
+        }
+
+        trailers.push((args.trailer_index("trailers", nth), trailer));
+    }
+
+    trailers.sort_by_key(|(index, _)| *index);
+
+    let mut start = 0;
+    for (nth, comment) in args.comments.iter().enumerate() {
+        let end = args.trailer_index(COMMENT_ARG, nth);
+        for (_, trailer) in trailers
+            .iter_mut()
+            .filter(|(i, _)| (start..end).contains(i))
+        {
+            trailer.comment = Some(comment.clone());
+        }
+
+        start = end;
+    }
+
+    trailers.into_iter().map(|(_, trailer)| trailer).collect()
+}
+
+/// Reads the trailers given on behalf of other people, in the order they are listed in `path`.
+fn get_on_behalf_trailers(path: &Path) -> Result<Vec<Trailer>, Error> {
+    let content = std::fs::read_to_string(path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+    let mut trailers = Vec::new();
+
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = || Error::InvalidTrailerLine(path.to_path_buf(), idx + 1, line.to_string());
+        let (key, value) = line.split_once(':').ok_or_else(invalid)?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+
+        let (value, comment) = split_trailer_comment(value.trim());
+        let mailbox = Mailbox::from_str(value).map_err(|_| invalid())?;
+        trailers.push(Trailer {
+            key: key.to_string(),
+            value: mailbox.to_string(),
+            comment: comment.map(String::from),
+        });
+    }
+
+    Ok(trailers)
+}
+
+/// Returns the URL of the original message in the list archives.
+fn get_link(cfg: &GitFile<'_>, msg: &mail_parser::Message<'_>) -> Result<String, Error> {
+    // Lists publishing Archived-At know better than us where the message ended up.
+    if let Some(url) = msg
+        .header_raw("Archived-At")
+        .map(|a| sanitize_header_value(a.trim().trim_start_matches('<').trim_end_matches('>')))
+        .filter(|a| !a.is_empty())
+    {
+        return Ok(url);
+    }
+
+    let msg_id = msg.message_id().ok_or(Error::MissingHeader("Message-ID"))?;
+
+    Ok(get_archive_link(cfg, &sanitize_header_value(msg_id)))
+}
+
+/// Returns the URL of the message `msg_id` in the archive of `acker.linkbase`.
+fn get_archive_link(cfg: &GitFile<'_>, msg_id: &str) -> String {
+    let base =
+        get_config_string(cfg, "acker.linkbase").unwrap_or_else(|| String::from(DEFAULT_LINK_BASE));
+
+    format!("{}/{msg_id}", base.trim_end_matches('/'))
+}
+
+/// The trailers of a reply, in the order they are written.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrailerSet(Vec<Trailer>);
+
+impl TrailerSet {
+    /// Adds a trailer after the others.
+    pub fn push(&mut self, trailer: Trailer) {
+        self.0.push(trailer);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Trailer> {
+        self.0.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Trailer> {
+        self.0.iter_mut()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Extend<Trailer> for TrailerSet {
+    fn extend<T: IntoIterator<Item = Trailer>>(&mut self, iter: T) {
+        self.0.extend(iter);
+    }
+}
+
+impl FromIterator<Trailer> for TrailerSet {
+    fn from_iter<T: IntoIterator<Item = Trailer>>(iter: T) -> Self {
+        TrailerSet(iter.into_iter().collect())
+    }
+}
+
+impl<'a> IntoIterator for &'a TrailerSet {
+    type Item = &'a Trailer;
+    type IntoIter = std::slice::Iter<'a, Trailer>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut TrailerSet {
+    type Item = &'a mut Trailer;
+    type IntoIter = std::slice::IterMut<'a, Trailer>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// One trailer per line, ready to be put in the reply.
+impl fmt::Display for TrailerSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for trailer in self {
+            writeln!(f, "{trailer}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the name to sign the reply with: `acker.signoffName` if set, or else the first name of
+/// the user.
+fn get_signoff_name(cfg: &GitFile<'_>, user: &Mailbox) -> String {
+    get_config_string(cfg, "acker.signoffName")
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| {
+            user.name
+                .as_ref()
+                .map_or(user.email.as_ref(), |n| n.split(' ').next().unwrap_or(n))
+                .to_string()
+        })
+}
+
+/// Returns the signature to append to the reply, from the file at `acker.signatureFile`, the
+/// text of `sendemail.signature` or else `~/.signature`.
+fn get_signature(cfg: &GitFile<'_>) -> Result<Option<String>, Error> {
+    let path = match cfg.path_by_key("acker.signatureFile") {
+        Some(p) => Some(
+            p.interpolate(PathContext {
+                home_dir: home::home_dir().as_deref(),
+                ..Default::default()
+            })
+            .map_err(|e| Error::InvalidConfig("acker.signatureFile", e.to_string()))?
+            .into_owned(),
+        ),
+        None => None,
+    };
+
+    let signature = match path {
+        Some(p) => Some(std::fs::read_to_string(&p).map_err(|e| Error::Io(p, e))?),
+        None => match get_config_string(cfg, "sendemail.signature") {
+            Some(s) => Some(s),
+            None => home::home_dir()
+                .map(|h| h.join(".signature"))
+                .filter(|p| p.exists())
+                .map(|p| std::fs::read_to_string(&p).map_err(|e| Error::Io(p, e)))
+                .transpose()?,
+        },
+    };
+
+    // Some signature files already start with the separator.
+    Ok(signature
+        .map(|s| {
+            let s = s.trim_end();
+            s.strip_prefix("-- \n").unwrap_or(s).to_string()
+        })
+        .filter(|s| !s.trim().is_empty()))
+}
+
+/// Makes sure the body ends with exactly one line break, whatever the parts we assembled left.
+fn normalize_body(body: &str) -> String {
+    let mut body = body.trim_end().to_string();
+
+    body.push('\n');
+    body
+}
+
+/// Who a reply is sent to.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Recipients {
+    pub to: Vec<Mailbox>,
+    pub cc: Vec<Mailbox>,
+    pub bcc: Vec<Mailbox>,
+}
+
+/// The parts of the reply the user can edit before it's sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Draft {
+    from: Mailbox,
+    sender: Option<Mailbox>,
+    subject: String,
+    recipients: Recipients,
+    body: String,
+}
+
+/// A reply ready to be sent.
+#[derive(Debug)]
+pub struct Reply {
+    pub message: Message,
+    /// The trailers added by the reply.
+    pub trailers: TrailerSet,
+    /// What the message was built from.
+    draft: Draft,
+    /// Whether the reply was meant for the author only, with `--author-only`.
+    author_only: bool,
+}
+
+/// Returns whether `a` and `b` are the same address, whatever the case they're written in.
+fn same_address(a: &Address, b: &Address) -> bool {
+    AsRef::<str>::as_ref(a).eq_ignore_ascii_case(b.as_ref())
+}
+
+/// Removes the mailboxes of `list` whose address is already in `seen`, or earlier in `list`,
+/// and adds the others to `seen`. A duplicate gives its name to a mailbox without one.
+fn dedup_mailboxes(list: &mut Vec<Mailbox>, seen: &mut Vec<Mailbox>) {
+    let mut kept: Vec<Mailbox> = Vec::new();
+    for mailbox in list.drain(..) {
+        if let Some(first) = kept
+            .iter_mut()
+            .find(|m| same_address(&m.email, &mailbox.email))
+        {
+            if first.name.is_none() {
+                first.name = mailbox.name;
+            }
+            continue;
+        }
+
+        if seen.iter().all(|m| !same_address(&m.email, &mailbox.email)) {
+            kept.push(mailbox);
+        }
+    }
+
+    seen.extend(kept.iter().cloned());
+    *list = kept;
+}
+
+/// Makes sure each address only shows once in `recipients`, in the most visible list it's in,
+/// and that there aren't more than `args` allows.
+fn check_recipients(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    recipients: &mut Recipients,
+) -> Result<(), Error> {
+    let mut seen = Vec::new();
+    for list in [&mut recipients.to, &mut recipients.cc, &mut recipients.bcc] {
+        dedup_mailboxes(list, &mut seen);
+    }
+
+    let count = seen.len();
+    if let Some(max) = args.max_recipients.filter(|max| count > *max) {
+        let err = Error::TooManyRecipients(count, max);
+        if !args.dry {
+            return Err(err);
+        }
+
+        eprintln!("acker: warning: {err}");
+    } else if count
+        > get_config_usize(cfg, "acker.warnRecipients")?.unwrap_or(DEFAULT_WARN_RECIPIENTS)
+    {
+        eprintln!(
+            "acker: warning: the reply goes to {count} people, check that it's the right message"
+        );
+    }
+
+    Ok(())
+}
+
+/// Gives the recipients their canonical address, so that the people posting from several of
+/// them only get the reply once.
+fn apply_mailmap(recipients: &mut Recipients, mailmap: &mailmap::Mailmap) {
+    let mut seen = Vec::new();
+
+    for list in [&mut recipients.to, &mut recipients.cc] {
+        let mapped = list.iter().map(|m| mailmap.map(m)).collect::<Vec<_>>();
+        list.clear();
+
+        for mailbox in mapped {
+            if !seen.contains(&mailbox.email) {
+                seen.push(mailbox.email.clone());
+                list.push(mailbox);
+            }
+        }
+    }
+}
+
+/// Returns the recipients to add to the ones of the original message: the ones of `--to`,
+/// `--cc` and `--bcc`, and unless the reply is private, the ones of `acker.cc` and `acker.bcc`,
+/// with their aliases expanded.
+fn get_extra_recipients(cfg: &GitFile<'_>, args: &Args) -> Result<Recipients, Error> {
+    let aliases = aliases::Aliases::load(cfg)?;
+    let mut extra = Recipients::default();
+    let (cc, bcc) = if args.author_only {
+        (Vec::new(), Vec::new())
+    } else {
+        (
+            get_config_strings(cfg, "acker.cc"),
+            get_config_strings(cfg, "acker.bcc"),
+        )
+    };
+
+    for (field, list, values) in [
+        ("--to", &mut extra.to, args.to.iter().collect::<Vec<_>>()),
+        ("--cc", &mut extra.cc, cc.iter().chain(&args.cc).collect()),
+        (
+            "--bcc",
+            &mut extra.bcc,
+            bcc.iter().chain(&args.bcc).collect(),
+        ),
+    ] {
+        for value in values {
+            list.extend(aliases.expand(field, value)?);
+        }
+    }
+
+    Ok(extra)
+}
+
+/// Merges the `extra` recipients of [`get_extra_recipients`] into `recipients`. An address only
+/// shows up once, in the most visible of the lists it's in.
+fn add_extra_recipients(recipients: &mut Recipients, extra: &Recipients) {
+    for mailbox in &extra.to {
+        if recipients.to.iter().all(|m| m.email != mailbox.email) {
+            recipients.cc.retain(|m| m.email != mailbox.email);
+            recipients.bcc.retain(|m| m.email != mailbox.email);
+            recipients.to.push(mailbox.clone());
+        }
+    }
+
+    for mailbox in &extra.cc {
+        if recipients
+            .to
+            .iter()
+            .chain(&recipients.cc)
+            .all(|m| m.email != mailbox.email)
+        {
+            recipients.bcc.retain(|m| m.email != mailbox.email);
+            recipients.cc.push(mailbox.clone());
+        }
+    }
+
+    for mailbox in &extra.bcc {
+        if recipients
+            .to
+            .iter()
+            .chain(&recipients.cc)
+            .chain(&recipients.bcc)
+            .all(|m| m.email != mailbox.email)
+        {
+            recipients.bcc.push(mailbox.clone());
+        }
+    }
+}
+
+/// Assembles the recipients of the reply: `author` in To, everyone else involved in Cc.
+fn get_recipients(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    msg: &mail_parser::Message<'_>,
+    author: Mailbox,
+    mailmap: &mailmap::Mailmap,
+) -> Result<Recipients, Error> {
+    if args.author_only {
+        let mut recipients = Recipients {
+            to: vec![author],
+            ..Default::default()
+        };
+
+        apply_mailmap(&mut recipients, mailmap);
+        add_extra_recipients(&mut recipients, &get_extra_recipients(cfg, args)?);
+        check_recipients(cfg, args, &mut recipients)?;
+        info!("only replying to the author, as --author-only asks");
+        log_recipients(&recipients);
+        return Ok(recipients);
+    }
+
+    let mut recipients = Recipients {
+        cc: get_mail_cc_list(cfg, args, msg, &author)?,
+        to: vec![author],
+        ..Default::default()
+    };
+
+    // The in-body From is the person we want to reach, whatever the headers say.
+    if !(args.ignore_reply_to || args.use_patch_author) {
+        apply_followup(cfg, msg, &mut recipients)?;
+    }
+
+    if args.respect_headers {
+        for mailbox in get_header_recipients(cfg, msg, "acker.toHeader", DEFAULT_TO_HEADER)? {
+            if recipients.to.iter().all(|m| m.email != mailbox.email) {
+                recipients.cc.retain(|m| m.email != mailbox.email);
+                recipients.to.push(mailbox);
+            }
+        }
+    }
+
+    // Some prefer the lists in To, so that followups keep going there, and only people in Cc.
+    if get_config_bool(cfg, "acker.listsInTo")?.unwrap_or(false) {
+        let lists = get_list_addresses(cfg, msg);
+        let (to, cc) = recipients
+            .cc
+            .into_iter()
+            .partition(|m| is_list_address(&lists, m));
+
+        recipients.cc = cc;
+        recipients.to.extend::<Vec<_>>(to);
+    }
+
+    apply_mailmap(&mut recipients, mailmap);
+    add_extra_recipients(&mut recipients, &get_extra_recipients(cfg, args)?);
+    check_recipients(cfg, args, &mut recipients)?;
+    log_recipients(&recipients);
+
+    Ok(recipients)
+}
+
+/// Logs the recipients we ended up with, with `-v`.
+fn log_recipients(recipients: &Recipients) {
+    for (field, list) in [
+        ("To", &recipients.to),
+        ("Cc", &recipients.cc),
+        ("Bcc", &recipients.bcc),
+    ] {
+        if !list.is_empty() {
+            let list: Vec<_> = list.iter().map(ToString::to_string).collect();
+            info!("{field}: {}", list.join(", "));
+        }
+    }
+}
+
+/// Builds the replies to messages, following the git configuration and the command line options.
+#[derive(Debug)]
+pub struct ReplyBuilder<'a> {
+    cfg: &'a GitFile<'static>,
+    args: &'a Args,
+    parent: Option<&'a mail_parser::Message<'a>>,
+}
+
+impl<'a> ReplyBuilder<'a> {
+    #[must_use]
+    pub fn new(cfg: &'a GitFile<'static>, args: &'a Args) -> Self {
+        ReplyBuilder {
+            cfg,
+            args,
+            parent: None,
+        }
+    }
+
+    /// Threads the replies under `parent` rather than under the messages they reply to.
+    #[must_use]
+    pub fn parent(mut self, parent: &'a mail_parser::Message<'a>) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Builds the reply to `msg`, or returns `None` if the user chose not to send it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `msg` or the configuration don't allow to build a valid reply.
+    pub fn build(&self, msg: &mail_parser::Message<'_>) -> Result<Option<Reply>, Error> {
+        build_reply(self.cfg, self.args, msg, self.parent)
+    }
+
+    /// Drafts the reply to `msg`, for the checks to look at before the user reviews it.
+    pub(crate) fn draft(
+        &self,
+        msg: &mail_parser::Message<'_>,
+    ) -> Result<(Draft, TrailerSet), Error> {
+        draft_reply(self.cfg, self.args, msg)
+    }
+
+    /// Lets the user review `draft`, and builds the reply to `msg` out of it.
+    pub(crate) fn review(
+        &self,
+        msg: &mail_parser::Message<'_>,
+        draft: Draft,
+        trailers: TrailerSet,
+    ) -> Result<Option<Reply>, Error> {
+        review_reply(self.cfg, self.args, msg, self.parent, draft, trailers)
+    }
+}
+
+/// Returns how the keys of the trailers we add are written, following `acker.trailerStyle`.
+fn get_trailer_style(cfg: &GitFile<'_>) -> Result<TrailerStyle, Error> {
+    Ok(get_config_string(cfg, "acker.trailerStyle")
+        .map(|s| {
+            TrailerStyle::from_str(&s).map_err(|()| Error::InvalidConfig("acker.trailerStyle", s))
+        })
+        .transpose()?
+        .unwrap_or_default())
+}
+
+/// Returns the trailers to add to the reply: ours, the ones on behalf of others and the link to
+/// the original message, in the configured style.
+fn get_reply_trailers(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    msg: &mail_parser::Message<'_>,
+    user: &Mailbox,
+) -> Result<TrailerSet, Error> {
+    let style = get_trailer_style(cfg)?;
+    let mut trailers = drop_original_tags(args, msg, get_trailers(args, user))?;
+    if let (true, Some(cmd)) = (args.record_test_cmd, get_test_cmd(cfg, args)) {
+        let user = user.to_string();
+        for trailer in &mut trailers {
+            if trailer.key == "Tested-by" && trailer.value == user && trailer.comment.is_none() {
+                trailer.comment = Some(format!("with {cmd}"));
+            }
+        }
+    }
+
+    if let Some(path) = &args.on_behalf_of {
+        let others = get_on_behalf_trailers(path)?;
+        if !others.is_empty() {
+            eprintln!(
+                "acker: warning: adding {} trailer(s) on behalf of other people, make sure they agreed to it",
+                others.len()
+            );
+        }
+
+        trailers.extend(others);
+    }
+
+    if args.link {
+        trailers.push(Trailer {
+            key: String::from("Link"),
+            value: get_link(cfg, msg)?,
+            comment: None,
+        });
+    }
+
+    for trailer in &mut trailers {
+        trailer.key = style.apply(&trailer.key);
+    }
+
+    Ok(trailers)
+}
+
+/// Returns the trailers of the reply, along with their text in the reply. With `--applied`, the
+/// text tells where the patch went instead.
+fn get_reply_tags(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    msg: &mail_parser::Message<'_>,
+    user: &Mailbox,
+    subject: &str,
+) -> Result<(TrailerSet, String), Error> {
+    if let Some(branch) = &args.applied {
+        return Ok((TrailerSet::default(), applied::get_text(branch, subject)?));
+    }
+
+    if args.nack {
+        let trailer = Trailer {
+            key: get_trailer_style(cfg)?.apply("Nacked-by"),
+            value: user.to_string(),
+            comment: None,
+        };
+
+        let text = get_nack_text(cfg, args, &trailer)?;
+        return Ok((std::iter::once(trailer).collect(), text));
+    }
+
+    let mut trailers = get_reply_trailers(cfg, args, msg, user)?;
+    if args.patch_tags.is_empty() {
+        let text = trailers.to_string();
+        return Ok((trailers, text));
+    }
+
+    // In the reply to the cover letter, each group of tags comes under the patches it's about.
+    let style = get_trailer_style(cfg)?;
+    let mut groups = Vec::new();
+    let mut scoped = Vec::new();
+    for tags in &args.patch_tags {
+        let mut group = format!("For {tags}:\n");
+        for key in &tags.keys {
+            let trailer = Trailer {
+                key: style.apply(key),
+                value: user.to_string(),
+                comment: None,
+            };
+
+            let _ = writeln!(group, "{trailer}");
+            scoped.push(Trailer {
+                comment: Some(format!("for {tags}")),
+                ..trailer
+            });
+        }
+
+        groups.push(group);
+    }
+
+    if !trailers.is_empty() {
+        groups.push(format!("For the whole series:\n{trailers}"));
+    }
+
+    trailers.extend(scoped);
+    Ok((trailers, groups.join("\n")))
+}
+
+/// Returns the text of a `--nack` reply: the reasons given with `--comment`, one paragraph each,
+/// and our `Nacked-by` trailer. With `--edit`, the user can write them instead.
+fn get_nack_text(cfg: &GitFile<'_>, args: &Args, trailer: &Trailer) -> Result<String, Error> {
+    let wrap_width = get_config_usize(cfg, "acker.wrapWidth")?.unwrap_or(DEFAULT_WRAP_WIDTH);
+    let mut text = String::from("I don't think this should be merged");
+    if args.comments.is_empty() {
+        text.push_str(".\n\n");
+    } else {
+        text.push_str(":\n\n");
+        for comment in &args.comments {
+            for line in wrap_line(comment, wrap_width) {
+                let _ = writeln!(text, "{line}");
+            }
+            text.push('\n');
+        }
+    }
+
+    let _ = writeln!(text, "{trailer}");
+    Ok(text)
+}
+
+/// The prefixes mail clients put in front of the subject of their replies, in a few languages.
+const REPLY_PREFIXES: [&str; 6] = ["re", "aw", "antw", "sv", "vs", "odp"];
+
+/// Returns the subject of our reply to a message sent as `subject`: a single `Re: ` whatever the
+/// reply prefixes it already has, and the rest left alone so that the `[PATCH vN i/M]` marker
+/// still matches the patch.
+fn get_reply_subject(subject: &str) -> String {
+    let mut rest = subject.trim_start();
+
+    while let Some((prefix, tail)) = rest.split_once(':') {
+        // Some clients count the replies, as in Re[2]: or Re^2:.
+        let word = prefix.split(['[', '^']).next().unwrap_or_default().trim();
+        if !REPLY_PREFIXES.iter().any(|p| word.eq_ignore_ascii_case(p)) {
+            break;
+        }
+
+        rest = tail.trim_start();
+    }
+
+    format!("Re: {rest}")
+}
+
+/// Returns the line before our name. The `--applied` text thanks for the patch already, and a
+/// nack isn't the place for thanks.
+fn get_reply_signoff<'a>(args: &Args, greeting: &'a locale::Greeting) -> &'a str {
+    if args.applied.is_some() || args.nack {
+        return "";
+    }
+
+    &greeting.signoff
+}
+
+/// Builds the message of `draft`, in reply to `parent`.
+/// Tells which part of the reply lettre refused. It only checks the headers once it gets the body,
+/// and the originator and the recipients are the only ones it checks.
+fn build_error(e: lettre::error::Error) -> Error {
+    let field = match e {
+        lettre::error::Error::MissingFrom | lettre::error::Error::TooManyFrom => "From",
+        lettre::error::Error::MissingTo => "To",
+        _ => "body",
+    };
+
+    Error::Build(field, e)
+}
+
+fn build_message(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    draft: &Draft,
+    parent: &mail_parser::Message<'_>,
+) -> Result<Message, Error> {
+    let mut builder = Message::builder()
+        .date_now()
+        .message_id(Some(make_message_id(&draft.from)))
+        .user_agent(format!("acker/{}", env!("CARGO_PKG_VERSION")))
+        .from(draft.from.clone())
+        .subject(draft.subject.clone());
+
+    if let Some(sender) = &draft.sender {
+        builder = builder.sender(sender.clone());
+    }
+
+    // The mail client sends the reply to the recipients of its headers, Bcc included.
+    if args.compose {
+        builder = builder.keep_bcc();
+    }
+
+    if !args.no_references {
+        let (in_reply_to, references) = get_threading(parent)?;
+
+        builder = builder.in_reply_to(in_reply_to).references(references);
+    }
+
+    for user in &draft.recipients.to {
+        builder = builder.to(user.clone());
+    }
+
+    for user in &draft.recipients.cc {
+        builder = builder.cc(user.clone());
+    }
+
+    for user in &draft.recipients.bcc {
+        builder = builder.bcc(user.clone());
+    }
+
+    // Our name, or the author one in the attribution, can be anything but ASCII.
+    let mut eml = if args.smime {
+        builder.multipart(sign::smime(cfg, &draft.body)?)
+    } else {
+        builder
+            .header(ContentType::TEXT_PLAIN)
+            .body(draft.body.clone())
+    }
+    .map_err(build_error)?;
+
+    if let Some(date) = get_reply_date(cfg, args)? {
+        eml.headers_mut().insert_raw(HeaderValue::new(
+            HeaderName::new_from_ascii_str("Date"),
+            date.to_rfc822(),
+        ));
+    }
+
+    // The signature covers the headers, so nothing can change them afterwards.
+    dkim::sign(cfg, &mut eml, draft.from.email.domain())?;
+
+    Ok(eml)
+}
+
+/// Drafts the reply to `msg`, with the trailers it gives, before the user gets to review it.
+fn draft_reply(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    msg: &mail_parser::Message<'_>,
+) -> Result<(Draft, TrailerSet), Error> {
+    let mailmap = mailmap::Mailmap::load(cfg)?;
+    let original_author = mailmap.map(&get_mail_author(args, msg)?);
+
+    let greeting = locale::get_greeting(cfg, args.locale.as_deref(), &original_author);
+
+    let user = get_user_mail(cfg)?;
+
+    // Even when starting a new thread, the subject keeps pointing at the original message.
+    let subject = unfold_header_value(msg.subject().ok_or(Error::MissingHeader("Subject"))?);
+
+    let (trailers, trailers_text) = get_reply_tags(cfg, args, msg, &user, &subject)?;
+
+    let date = get_message_date(cfg, msg)?.unwrap_or_default();
+    let author = original_author
+        .name
+        .clone()
+        .unwrap_or(original_author.email.to_string());
+    let greeting_text = greeting
+        .body_prefix
+        .as_ref()
+        .map(|p| format!("{p}\n\n"))
+        .unwrap_or_default();
+
+    let mut reply_text = template::render(
+        &template::get_template(cfg)?,
+        &[
+            ("author", &author),
+            ("author_email", original_author.email.as_ref()),
+            ("date", &date),
+            ("subject", &subject),
+            ("greeting", &greeting_text),
+            ("attribution", &get_attribution(cfg, msg, &original_author)?),
+            ("quoted_body", &get_quoted_body(cfg, args, msg)?),
+            ("trailers", &trailers_text),
+            ("signoff", get_reply_signoff(args, &greeting)),
+            ("first_name", &get_signoff_name(cfg, &user)),
+        ],
+    )?;
+
+    if !args.no_signature {
+        if let Some(signature) = get_signature(cfg)? {
+            let _ = write!(reply_text, "\n-- \n{signature}\n");
+        }
+    }
+
+    let (from, sender) = get_reply_from(cfg, args, user)?;
+    let draft = Draft {
+        from,
+        sender,
+        subject: get_reply_subject(&subject),
+        recipients: get_recipients(cfg, args, msg, original_author, &mailmap)?,
+        body: reply_text,
+    };
+
+    Ok((draft, trailers))
+}
+
+/// Lets the user review `draft`, and assembles the reply to `msg` out of it, or returns `None`
+/// if the user chose not to send it.
+fn review_reply(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    msg: &mail_parser::Message<'_>,
+    parent: Option<&mail_parser::Message<'_>>,
+    mut draft: Draft,
+    trailers: TrailerSet,
+) -> Result<Option<Reply>, Error> {
+    if args.confirm_recipients {
+        draft.recipients = editor::edit_recipients(cfg, &draft.recipients)?;
+    }
+
+    if args.edit {
+        draft = editor::edit_reply(cfg, &draft)?;
+    }
+
+    if !(args.dry || args.compose) && args.confirm {
+        draft = match prompt::confirm_reply(cfg, draft)? {
+            Some(draft) => draft,
+            None => return Ok(None),
+        };
+    }
+
+    if draft.recipients.to.is_empty() {
+        return Err(Error::NoRecipients);
+    }
+
+    // The signature covers what is sent, so it has to come after any edit.
+    draft.body = normalize_body(&draft.body);
+    if args.sign {
+        draft.body = sign::clearsign(cfg, &draft.body)?;
+    }
+
+    let eml = build_message(cfg, args, &draft, parent.unwrap_or(msg))?;
+
+    Ok(Some(Reply {
+        message: eml,
+        trailers,
+        draft,
+        author_only: args.author_only,
+    }))
+}
+
+/// Assembles the reply to `msg`, or returns `None` if the user chose not to send it.
+fn build_reply(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    msg: &mail_parser::Message<'_>,
+    parent: Option<&mail_parser::Message<'_>>,
+) -> Result<Option<Reply>, Error> {
+    let (draft, trailers) = draft_reply(cfg, args, msg)?;
+    review_reply(cfg, args, msg, parent, draft, trailers)
+}
+
+/// Runs the acker command given in `cli`.
+///
+/// # Errors
+///
+/// Returns the first error met, whose [`Error::exit_code`] tells what went wrong.
+pub fn run(cli: &Cli) -> Result<ExitCode, Error> {
+    if cli.trace_smtp || cli.verbose > 0 {
+        trace::enable(cli.trace_smtp, cli.verbose);
+    }
+
+    let mut cfg = GitFile::from_globals().map_err(|e| Error::Config(e.to_string()))?;
+    project::apply(&mut cfg)?;
+
+    run_with(cli, cfg, None)
+}
+
+/// Runs the acker command given in `cli` like [`run`], but with `cfg` rather than the git
+/// configuration, and through `transport` if it's given rather than the one `cfg` asks for.
+/// With a [`MailTransport::Capture`], this is how the tests look at the replies acker sends.
+///
+/// # Errors
+///
+/// Returns the first error met, whose [`Error::exit_code`] tells what went wrong.
+pub fn run_with(
+    cli: &Cli,
+    mut cfg: GitFile<'static>,
+    transport: Option<MailTransport>,
+) -> Result<ExitCode, Error> {
+    // The profile picks the sendemail identity, but its own keys have the last word.
+    let profile = get_profile(&cfg, cli.profile.as_deref())?;
+    let identity = profile
+        .iter()
+        .find(|(s, k, _)| *s == "sendemail" && k == "identity")
+        .map(|(_, _, v)| v.as_str());
+    apply_identity(&mut cfg, cli.identity.as_deref().or(identity))?;
+    apply_profile(&mut cfg, &profile)?;
+    if let Some(profile) = cli
+        .profile
+        .clone()
+        .or_else(|| get_config_string(&cfg, "acker.profile"))
+    {
+        info!("using the acker.{profile} profile");
+    }
+
+    match &cli.command {
+        Some(Command::Reply(args)) => reply(&cfg, cli, &args.with_config(&cfg)?, None, transport),
+        Some(Command::Fetch {
+            target,
+            reply: args,
+        }) => reply(&cfg, cli, &args.with_config(&cfg)?, Some(target), transport),
+        Some(Command::SendQueue) => {
+            let transport = match transport {
+                Some(transport) => transport,
+                None => MailTransport::with_kind(&cfg, cli.transport)?,
+            };
+
+            outbox::flush(&cfg, &transport)
+        }
+        Some(Command::Notes { revision, link }) => notes::record(&cfg, revision, *link),
+        Some(Command::Init) => init::run(&cfg),
+        Some(Command::Stats { since }) => stats::run(*since),
+        Some(Command::Completions { shell }) => generate::completions(*shell),
+        Some(Command::Man { dir }) => generate::man(dir.as_deref()),
+        None => reply(&cfg, cli, &cli.reply.with_config(&cfg)?, None, transport),
+    }
+}
+
+/// Returns the messages of `messages`, read from `source`, that get a reply, in the order of the
+/// series.
+fn select_messages<'a>(
+    args: &Args,
+    source: &Path,
+    mut messages: Vec<mail_parser::Message<'a>>,
+) -> Result<Vec<mail_parser::Message<'a>>, Error> {
+    // A thread also has the reviews of the other people, we only want to reply to the patches.
+    if args.thread {
+        series::retain_patches(&mut messages);
+    }
+
+    series::sort(&mut messages);
+    if let Some(last) = args.patch_tags.iter().map(PatchTags::last).max() {
+        let len = series::len(&messages);
+        if last > len {
+            return Err(Error::NotInSeries(last, len));
+        }
+    }
+
+    if args.cover_letter || !args.patch_tags.is_empty() {
+        let index = series::find_cover_letter(&messages)
+            .ok_or_else(|| Error::NoCoverLetter(source.to_path_buf()))?;
+
+        messages = vec![messages.swap_remove(index)];
+    } else if messages.len() > 1 {
+        // We reply to each patch, so the cover letter doesn't need a reply of its own.
+        if let Some(index) = series::find_cover_letter(&messages) {
+            messages.remove(index);
+        }
+    }
+
+    Ok(messages)
+}
+
+/// Runs the sanity checks on the `draft` of the reply to `msg` giving `trailers`, before the
+/// user is asked anything about it. Returns whether to skip it.
+fn check_draft(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    log: &state::SentLog,
+    thread: Option<&thread::Thread>,
+    msg: &mail_parser::Message<'_>,
+    draft: &Draft,
+    trailers: &TrailerSet,
+) -> Result<bool, Error> {
+    if check_duplicates(args, log, thread, msg, get_draft_user(draft), trailers)? {
+        return Ok(true);
+    }
+
+    check_from_domain(cfg, args, draft)?;
+    check_stale(cfg, args, msg)?;
+    check_patch(args, msg)?;
+    check_already_acked(args, log, msg, trailers)?;
+    check_nacked(args, log, msg);
+
+    Ok(false)
+}
+
+/// Checks again what the review of `reply` might have changed since its `draft`: who we send
+/// it as. Returns whether to skip it.
+fn check_reviewed(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    thread: Option<&thread::Thread>,
+    msg: &mail_parser::Message<'_>,
+    draft: &Draft,
+    reply: &Reply,
+) -> Result<bool, Error> {
+    let user = get_draft_user(&reply.draft);
+    if user == get_draft_user(draft) {
+        return Ok(false);
+    }
+
+    check_from_domain(cfg, args, &reply.draft)?;
+
+    // What we sent before doesn't depend on the address, the thread does.
+    let duplicates = match thread {
+        Some(thread) => thread.find_duplicates(args, msg, user, &reply.trailers)?,
+        None => Vec::new(),
+    };
+
+    Ok(report_duplicates(args, msg, duplicates))
+}
+
+/// Prints the trailers of `reply` for scripts, with `--report-trailers`.
+fn report_trailers(reply: &Reply) {
+    for trailer in &reply.trailers {
+        eprintln!("trailer\t{}\t{}", trailer.key, trailer.value);
+    }
+}
+
+/// Sends the `replies` to their messages, and keeps track of the ones that went out. A reply
+/// that couldn't be sent doesn't stop the others, and goes to the outbox so that it isn't lost.
+fn send_replies(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    transport: &MailTransport,
+    replies: &[(&mail_parser::Message<'_>, Reply)],
+    bookkeeping: &mut Bookkeeping,
+) -> Result<(), Error> {
+    let emails: Vec<_> = replies
+        .iter()
+        .map(|(_, r)| (r.message.envelope().clone(), r.message.formatted()))
+        .collect();
+    let results = batch::send_all(transport, &emails, batch::get_concurrency(cfg, transport)?);
+
+    let mut failed = Vec::new();
+    for ((msg, reply), result) in replies.iter().zip(results) {
+        match result {
+            Ok(()) => {
+                bookkeeping.sent(msg, reply);
+                if args.report_trailers {
+                    report_trailers(reply);
+                }
+            }
+            Err(e) => {
+                let e = match outbox::queue(cfg, &reply.message) {
+                    Ok(path) => {
+                        bookkeeping.queued(msg, &reply.trailers);
+                        format!("{e}, it's in {} for acker send-queue", path.display())
+                    }
+                    Err(queue_err) => {
+                        eprintln!("acker: warning: {queue_err}");
+                        e
+                    }
+                };
+
+                failed.push((reply, e));
+            }
+        }
+    }
+
+    match failed.as_slice() {
+        [] => Ok(()),
+        [(_, e)] if replies.len() == 1 => Err(Error::Send(e.clone())),
+        _ => {
+            for (reply, e) in &failed {
+                eprintln!(
+                    "acker: warning: couldn't send \"{}\": {e}",
+                    reply.draft.subject
+                );
+            }
+
+            Err(Error::Send(format!(
+                "{} of the {} replies failed",
+                failed.len(),
+                replies.len()
+            )))
+        }
+    }
+}
+
+/// Pairs the messages to reply to with the options of their reply: `args`, or with `--select`,
+/// the ones with the tags chosen for each patch.
+#[cfg_attr(not(feature = "tui"), allow(clippy::unnecessary_wraps))]
+fn get_patch_args<'a, 'm>(
+    args: &'a Args,
+    messages: &'a [mail_parser::Message<'m>],
+) -> Result<Vec<(&'a mail_parser::Message<'m>, Cow<'a, Args>)>, Error> {
+    #[cfg(feature = "tui")]
+    if args.select {
+        return select::choose(args, messages);
+    }
+
+    Ok(messages.iter().map(|m| (m, Cow::Borrowed(args))).collect())
+}
+
+/// Returns the transport to send the replies through, `transport` or else the one of the
+/// configuration, if they're sent rather than printed or queued.
+fn get_reply_transport(
+    cfg: &GitFile<'_>,
+    cli: &Cli,
+    args: &Args,
+    transport: Option<MailTransport>,
+) -> Result<Option<MailTransport>, Error> {
+    if args.dry || args.queue || args.compose {
+        return Ok(None);
+    }
+
+    let transport = match transport {
+        Some(transport) => transport,
+        None => MailTransport::with_kind(cfg, cli.transport)?,
+    };
+    if cli.trace_smtp && !matches!(transport, MailTransport::Smtp(_)) {
+        eprintln!("acker: warning: only the SMTP transport has a dialogue to trace");
+    }
+
+    Ok(Some(transport))
+}
+
+/// Returns where the messages to reply to come from, and the messages: the ones given in
+/// `args`, or the one to `fetch` from the archive.
+fn get_input(
+    cfg: &GitFile<'_>,
+    args: &Args,
+    fetch: Option<&String>,
+) -> Result<(PathBuf, Vec<Vec<u8>>), Error> {
+    let (source, buffers) = match fetch.or(args.fetch.as_ref()) {
+        Some(target) => {
+            let url = fetch::get_url(cfg, target, args.thread);
+            let source = PathBuf::from(&url);
+            let buffers = split_mbox(&source, fetch::fetch(&url)?)?;
+
+            (source, buffers)
+        }
+        None => (args.input.clone(), read_messages(&args.input)?),
+    };
+
+    // Some submitters attach their patch, we review it as if it were inline.
+    Ok((
+        source,
+        buffers.into_iter().map(attachment::inline).collect(),
+    ))
+}
+
+/// Replies to the messages given in `args`, or to the one to `fetch` from the archive, through
+/// `transport` if it's given.
+fn reply(
+    cfg: &GitFile<'static>,
+    cli: &Cli,
+    args: &Args,
+    fetch: Option<&String>,
+    transport: Option<MailTransport>,
+) -> Result<ExitCode, Error> {
+    let (source, buffers) = get_input(cfg, args, fetch)?;
+    let messages = buffers
+        .iter()
+        .map(|b| {
+            MessageParser::default()
+                .parse(b)
+                .ok_or_else(|| Error::InvalidMessage(source.clone()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let session = session::Session::load(&messages)?;
+    let messages = select_messages(args, &source, messages)?;
+
+    let parent_buffer = args
+        .parent
+        .as_ref()
+        .map(|p| std::fs::read(p).map_err(|e| Error::Io(p.clone(), e)))
+        .transpose()?;
+    let parent = match (&args.parent, &parent_buffer) {
+        (Some(path), Some(buffer)) => Some(parse_parent(path, buffer)?),
+        _ => None,
+    };
+
+    let transport = get_reply_transport(cfg, cli, args, transport)?;
+
+    if args.check_apply {
+        check_apply(args, &messages)?;
+    }
+
+    let mut worktree = get_test_cmd(cfg, args).map(patch::Worktree::new);
+    let mut bookkeeping = Bookkeeping::new(cfg, args, session)?;
+    let pre_send_hook = get_config_string(cfg, "acker.preSendHook");
+    let mut pending = Vec::new();
+    let mut thread = None;
+
+    for (msg, args) in get_patch_args(args, &messages)? {
+        let args = args.as_ref();
+        if args.resume && check_resumed(&bookkeeping.session, msg) {
+            continue;
+        }
+
+        let mut builder = ReplyBuilder::new(cfg, args);
+        if let Some(parent) = &parent {
+            builder = builder.parent(parent);
+        }
+
+        if let Some(worktree) = &mut worktree {
+            test_patch(args, worktree, msg)?;
+        }
+
+        // The patches of a series share their thread, it only needs to be read once.
+        if args.show_thread.is_some() || args.skip_if_tagged {
+            let source = args.show_thread.as_deref().unwrap_or(Path::new(""));
+            let thread = match &mut thread {
+                Some(thread) => thread,
+                None => thread.insert(thread::Thread::load(cfg, source, msg)?),
+            };
+
+            // stdout is ours for the reply, with --dry-run or --compose.
+            if args.show_thread.is_some() {
+                eprint!("{}", thread.format_replies(args, msg)?);
+            }
+        }
+
+        // The checks come before the editor and the prompts, not to refuse a reviewed reply.
+        let (draft, trailers) = builder.draft(msg)?;
+        let log = &bookkeeping.log;
+        if check_draft(cfg, args, log, thread.as_ref(), msg, &draft, &trailers)? {
+            continue;
+        }
+
+        let Some(reply) = builder.review(msg, draft.clone(), trailers)? else {
+            continue;
+        };
+        if check_reviewed(cfg, args, thread.as_ref(), msg, &draft, &reply)? {
+            continue;
+        }
+        let eml = &reply.message;
+
+        match &transport {
+            // The replies are all sent at once, after the questions we might have for the user.
+            Some(transport) => {
+                if let Some(hook) = &pre_send_hook {
+                    hook::run(hook, &reply, msg.message_id())?;
+                }
+
+                pending.push((msg, reply));
+                // Another interruption shouldn't lose the replies of the session either.
+                if args.resume {
+                    send_replies(cfg, args, transport, &pending, &mut bookkeeping)?;
+                    pending.clear();
+                }
+                continue;
+            }
+            None if args.queue => {
+                let path = outbox::queue(cfg, eml)?;
+                eprintln!("acker: queued the reply as {}", path.display());
+                bookkeeping.queued(msg, &reply.trailers);
+            }
+            None => std::io::stdout()
+                .write_all(&output::format(args.output_format, &reply))
+                .map_err(|e| Error::Io(PathBuf::from("<stdout>"), e))?,
+        }
+
+        if args.report_trailers {
+            report_trailers(&reply);
+        }
+    }
+
+    if let Some(transport) = transport.as_ref().filter(|_| !pending.is_empty()) {
+        send_replies(cfg, args, transport, &pending, &mut bookkeeping)?;
+    }
+
+    if args.dry && args.exit_code {
+        return Ok(ExitCode::from(EXIT_DRY_RUN_OK));
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Parses `buffer`, the message at `path` given with `--parent`, which needs a Message-ID for
+/// the reply to point at it.
+fn parse_parent<'a>(path: &Path, buffer: &'a [u8]) -> Result<mail_parser::Message<'a>, Error> {
+    let parent = MessageParser::default()
+        .parse(buffer)
+        .ok_or_else(|| Error::InvalidMessage(path.to_path_buf()))?;
+    if parent.message_id().is_none() {
+        return Err(Error::NoParentMessageId(path.to_path_buf()));
+    }
+
+    Ok(parent)
+}
+
+/// Reads the messages to reply to: the one on stdin if `input` is `-`, or else the one in the
+/// file, or all of them if it's an mbox or a Maildir.
+fn read_messages(input: &Path) -> Result<Vec<Vec<u8>>, Error> {
+    let mut buffer = Vec::new();
+
+    if input == Path::new("-") {
+        std::io::stdin()
+            .lock()
+            .read_to_end(&mut buffer)
+            .map_err(|e| Error::Io(input.to_path_buf(), e))?;
+
+        return Ok(vec![buffer]);
+    }
+
+    if input.is_dir() {
+        return maildir::MessageIterator::new(input)
+            .and_then(|messages| {
+                messages
+                    .map(|m| m.map(maildir::Message::unwrap_contents))
+                    .collect()
+            })
+            .map_err(|e| Error::Io(input.to_path_buf(), e));
+    }
+
+    buffer = std::fs::read(input).map_err(|e| Error::Io(input.to_path_buf(), e))?;
+
+    split_mbox(input, buffer)
+}
+
+/// Splits `buffer` in the messages it contains if it's an mbox, or else returns it as is.
+fn split_mbox(input: &Path, buffer: Vec<u8>) -> Result<Vec<Vec<u8>>, Error> {
+    if !buffer.starts_with(b"From ") {
+        return Ok(vec![buffer]);
+    }
+
+    MessageIterator::new(buffer.as_slice())
+        .map(|m| {
+            m.map(mail_parser::mailbox::mbox::Message::unwrap_contents)
+                .map_err(|_| Error::InvalidMessage(input.to_path_buf()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points the home and XDG directories to an empty one, so that the signature and the
+    /// template of whoever runs the tests don't end up in the replies.
+    fn isolate() {
+        static HOME: std::sync::OnceLock<tempfile::TempDir> = std::sync::OnceLock::new();
+
+        HOME.get_or_init(|| {
+            let home = tempfile::tempdir().unwrap();
+            for var in ["HOME", "XDG_CONFIG_HOME", "XDG_DATA_HOME", "XDG_STATE_HOME"] {
+                std::env::set_var(var, home.path());
+            }
+
+            home
+        });
+    }
+
+    fn config() -> GitFile<'static> {
+        GitFile::from_str("[user]\n\tname = Maxime Ripard\n\temail = mripard@kernel.org\n").unwrap()
+    }
+
+    fn user() -> Mailbox {
+        Mailbox::from_str("Maxime Ripard <mripard@kernel.org>").unwrap()
+    }
+
+    #[test]
+    fn repeated_trailers_are_kept_in_order() {
+        let args = Args::try_parse_from([
+            "acker",
+            "--trailer",
+            "Tested-by=A <a@example.com>",
+            "--trailer",
+            "Reviewed-by=C <c@example.com>",
+            "--trailer",
+            "Tested-by=B <b@example.com>",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            get_trailers(&args, &user()).to_string(),
+            "Tested-by: A <a@example.com>\n\
+             Reviewed-by: C <c@example.com>\n\
+             Tested-by: B <b@example.com>\n"
+        );
+    }
+
+    #[test]
+    fn trailers_follow_the_command_line_order() {
+        let args = Args::try_parse_ordered([
+            "acker",
+            "--trailer",
+            "Suggested-by",
+            "-r",
+            "--trailer",
+            "Fixes=1234567890ab (\"drm/foo: add the frobnicator\")",
+            "-s",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            get_trailers(&args, &user()).to_string(),
+            "Suggested-by: Maxime Ripard <mripard@kernel.org>\n\
+             Reviewed-by: Maxime Ripard <mripard@kernel.org>\n\
+             Fixes: 1234567890ab (\"drm/foo: add the frobnicator\")\n\
+             Signed-off-by: Maxime Ripard <mripard@kernel.org>\n"
+        );
+    }
+
+    #[test]
+    fn comment_scopes_the_trailers_before_it() {
+        let args =
+            Args::try_parse_ordered(["acker", "-a", "-r", "--comment", "for the DT bits", "-t"])
+                .unwrap();
+
+        assert_eq!(
+            get_trailers(&args, &user()).to_string(),
+            "Acked-by: Maxime Ripard <mripard@kernel.org> # for the DT bits\n\
+             Reviewed-by: Maxime Ripard <mripard@kernel.org> # for the DT bits\n\
+             Tested-by: Maxime Ripard <mripard@kernel.org>\n"
+        );
+
+        assert!(Args::try_parse_ordered(["acker", "--comment", "for the DT bits", "-a"]).is_err());
+    }
+
+    #[test]
+    fn nack_comes_with_its_reasons() {
+        assert!(Args::try_parse_ordered(["acker", "--nack"]).is_err());
+        assert!(Args::try_parse_ordered(["acker", "--nack", "--edit"]).is_ok());
+
+        let args = Args::try_parse_ordered(["acker", "--nack", "--comment", "this breaks the ABI"])
+            .unwrap();
+        let trailer = Trailer {
+            key: String::from("Nacked-by"),
+            value: user().to_string(),
+            comment: None,
+        };
+
+        assert_eq!(
+            get_nack_text(&config(), &args, &trailer).unwrap(),
+            "I don't think this should be merged:\n\n\
+             this breaks the ABI\n\n\
+             Nacked-by: Maxime Ripard <mripard@kernel.org>\n"
+        );
+    }
+
+    #[test]
+    fn patch_checks_find_the_missing_signoff() {
+        let author = Mailbox::from_str("Jane Doe <jane@example.com>").unwrap();
+        let diff = "---\n foo.c | 2 +-\n\n\
+                    diff --git a/foo.c b/foo.c\n\
+                    --- a/foo.c\n\
+                    +++ b/foo.c\n\
+                    @@ -1 +1 @@\n\
+                    -frob();\n\
+                    +if (!bar) frob();\n";
+
+        let signed =
+            format!("Fix the frobnicator.\n\nSigned-off-by: Jane Doe <jane@example.com>\n{diff}");
+        assert!(patch::check("[PATCH] foo: fix", &signed, &author).is_empty());
+
+        let unsigned = format!("Fix the frobnicator.\n{diff}");
+        assert_eq!(
+            patch::check("[PATCH] foo: fix", &unsigned, &author),
+            ["the patch has no Signed-off-by line"]
+        );
+
+        assert!(patch::check("Re: foo", "Looks good to me.\n", &author).is_empty());
+    }
+
+    #[test]
+    fn patch_tags_are_described() {
+        let tags = parse_patch_tags("1-3,5:reviewed,Tested-by").unwrap();
+        assert_eq!(tags.keys, ["Reviewed-by", "Tested-by"]);
+        assert_eq!(tags.last(), 5);
+        assert_eq!(tags.to_string(), "patches 1-3, 5");
+
+        assert_eq!(parse_patch_tags("4:acked").unwrap().to_string(), "patch 4");
+        assert!(parse_patch_tags("0:acked").is_err());
+        assert!(parse_patch_tags("3-1:acked").is_err());
+        assert!(parse_patch_tags("1:nacked").is_err());
+    }
+
+    #[test]
+    fn mail_followup_to_drives_the_recipients() {
+        isolate();
+
+        let cfg = config();
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    To: Maxime Ripard <mripard@kernel.org>\r\n\
+                    Cc: Bob <bob@example.org>\r\n\
+                    Mail-Followup-To: Jane Doe <jane@example.com>, dri-devel@example.org\r\n\
+                    Subject: [PATCH] foo: fix\r\n\
+                    Date: Mon, 12 Oct 2026 10:00:00 +0200\r\n\
+                    Message-ID: <20261012.1234-1-jane@example.com>\r\n\
+                    \r\n\
+                    Fix the frobnicator.\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+
+        let args = Args::try_parse_from(["acker", "-r"]).unwrap();
+        let reply = build_reply(&cfg, &args, &msg, None).unwrap().unwrap();
+        let to: Vec<_> = reply
+            .draft
+            .recipients
+            .to
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(to, ["Jane Doe <jane@example.com>", "dri-devel@example.org"]);
+        assert_eq!(reply.draft.recipients.cc, [user()]);
+
+        let args = Args::try_parse_from(["acker", "-r", "--ignore-reply-to"]).unwrap();
+        let reply = build_reply(&cfg, &args, &msg, None).unwrap().unwrap();
+        assert_eq!(reply.draft.recipients.to.len(), 1);
+        assert_eq!(reply.draft.recipients.cc.len(), 2);
+    }
+
+    #[test]
+    fn recipients_are_deduplicated_by_address() {
+        isolate();
+
+        let cfg = config();
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    To: dri-devel@example.org\r\n\
+                    Cc: BOB@Example.org, Robert <bob@example.org>, JANE@example.com\r\n\
+                    Subject: [PATCH] foo: fix\r\n\
+                    Date: Mon, 12 Oct 2026 10:00:00 +0200\r\n\
+                    Message-ID: <20261012.1234-1-jane@example.com>\r\n\
+                    \r\n\
+                    Fix the frobnicator.\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+
+        let args = Args::try_parse_from(["acker", "-r", "--bcc", "Bob@example.org"]).unwrap();
+        let reply = build_reply(&cfg, &args, &msg, None).unwrap().unwrap();
+        let cc: Vec<_> = reply
+            .draft
+            .recipients
+            .cc
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            cc,
+            [
+                "Robert <BOB@Example.org>",
+                "dri-devel@example.org",
+                "Maxime Ripard <mripard@kernel.org>"
+            ]
+        );
+        assert!(reply.draft.recipients.bcc.is_empty());
+
+        let args = Args::try_parse_from(["acker", "-r", "--max-recipients", "3"]).unwrap();
+        assert!(matches!(
+            build_reply(&cfg, &args, &msg, None),
+            Err(Error::TooManyRecipients(4, 3))
+        ));
+    }
+
+    #[test]
+    fn trailers_of_the_patch_are_merged() {
+        isolate();
+
+        let cfg = config();
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    To: dri-devel@example.org\r\n\
+                    Subject: [PATCH v2] foo: fix\r\n\
+                    Date: Mon, 12 Oct 2026 10:00:00 +0200\r\n\
+                    Message-ID: <20261012.1234-1-jane@example.com>\r\n\
+                    \r\n\
+                    Fix the frobnicator.\r\n\
+                    \r\n\
+                    Cc: stable@example.org # 5.10\r\n\
+                    Reviewed-by: Maxime Ripard <mripard@kernel.org>\r\n\
+                    Signed-off-by: Jane Doe <jane@example.com>\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+
+        let args = Args::try_parse_from(["acker", "-r", "-a", "--cc-trailers"]).unwrap();
+        let reply = build_reply(&cfg, &args, &msg, None).unwrap().unwrap();
+        assert_eq!(
+            reply.trailers.to_string(),
+            "Acked-by: Maxime Ripard <mripard@kernel.org>\n"
+        );
+        assert!(reply
+            .draft
+            .recipients
+            .cc
+            .iter()
+            .any(|m| m.email.to_string() == "stable@example.org"));
+    }
+
+    #[test]
+    fn attribution_date_follows_the_configuration() {
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    Subject: [PATCH] foo: fix\r\n\
+                    Date: Mon, 12 Oct 2026 10:00:00 +0200\r\n\
+                    \r\n\
+                    Fix the frobnicator.\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+        let author = Mailbox::from_str("Jane Doe <jane@example.com>").unwrap();
+
+        assert_eq!(
+            get_attribution(&config(), &msg, &author).unwrap(),
+            "On Mon, 12 Oct 2026 10:00:00 +0200, Jane Doe wrote:"
+        );
+
+        let cfg = GitFile::from_str(
+            "[acker]\n\tattributionTimezone = -0130\n\tdateFmt = %Y-%m-%d %H:%M %z\n",
+        )
+        .unwrap();
+        assert_eq!(
+            get_attribution(&cfg, &msg, &author).unwrap(),
+            "On 2026-10-12 06:30 -0130, Jane Doe wrote:"
+        );
+
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    Date: yesterday\r\n\
+                    \r\n\
+                    Fix the frobnicator.\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+        assert_eq!(
+            get_attribution(&cfg, &msg, &author).unwrap(),
+            "Jane Doe wrote:"
+        );
+    }
+
+    #[test]
+    fn html_only_body_is_quoted_as_text() {
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    Subject: Re: [PATCH] foo: fix\r\n\
+                    MIME-Version: 1.0\r\n\
+                    Content-Type: multipart/alternative; boundary=\"b\"\r\n\
+                    \r\n\
+                    --b\r\n\
+                    Content-Type: text/plain\r\n\
+                    \r\n\
+                    \r\n\
+                    --b\r\n\
+                    Content-Type: text/html\r\n\
+                    \r\n\
+                    <p>Looks good &amp; works.</p>\r\n\
+                    --b--\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+
+        let args = Args::try_parse_from(["acker"]).unwrap();
+        assert_eq!(
+            get_mail_body(&args, &msg).unwrap().trim(),
+            "Looks good & works."
+        );
+
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    Content-Type: application/octet-stream\r\n\
+                    \r\n\
+                    AAAA\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+        assert!(matches!(
+            get_mail_body(&args, &msg),
+            Err(Error::UnsupportedBody)
+        ));
+    }
+
+    #[test]
+    fn attached_patch_is_quoted_inline() {
+        let raw = b"From: Bob <bob@example.org>\r\n\
+                    Subject: Fwd: fix\r\n\
+                    Message-ID: <fwd@example.org>\r\n\
+                    MIME-Version: 1.0\r\n\
+                    Content-Type: multipart/mixed; boundary=\"b\"\r\n\
+                    \r\n\
+                    --b\r\n\
+                    Content-Type: text/plain\r\n\
+                    \r\n\
+                    \r\n\
+                    --b\r\n\
+                    Content-Type: message/rfc822\r\n\
+                    \r\n\
+                    From: Jane Doe <jane@example.com>\r\n\
+                    Subject: [PATCH] foo: fix\r\n\
+                    \r\n\
+                    Fix the frobnicator.\r\n\
+                    ---\r\n\
+                    diff --git a/foo.c b/foo.c\r\n\
+                    --b--\r\n";
+        let buffer = attachment::inline(raw.to_vec());
+        let msg = MessageParser::default().parse(&buffer).unwrap();
+
+        assert_eq!(msg.subject(), Some("[PATCH] foo: fix"));
+        assert_eq!(msg.message_id(), Some("fwd@example.org"));
+
+        let args = Args::try_parse_from(["acker", "--use-patch-author"]).unwrap();
+        assert_eq!(
+            get_mail_author(&args, &msg).unwrap().to_string(),
+            "Jane Doe <jane@example.com>"
+        );
+        assert!(get_mail_body(&args, &msg)
+            .unwrap()
+            .lines()
+            .any(|l| l == "Fix the frobnicator."));
+    }
+
+    #[test]
+    fn bodies_are_decoded_from_their_charset() {
+        let args = Args::try_parse_from(["acker"]).unwrap();
+
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    Content-Type: text/plain; charset=koi8-r\r\n\
+                    Content-Transfer-Encoding: 8bit\r\n\
+                    \r\n\
+                    \xf0\xd2\xc9\xd7\xc5\xd4\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+        assert_eq!(get_mail_body(&args, &msg).unwrap().trim(), "Привет");
+
+        // Some mailers send 8-bit text without telling which charset it's in.
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    \r\n\
+                    \xc7a marche tr\xe8s bien\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+        assert_eq!(
+            get_mail_body(&args, &msg).unwrap().trim(),
+            "Ça marche très bien"
+        );
+    }
+
+    #[test]
+    fn quotes_nest_and_flowed_text_is_joined() {
+        let text = quote::unflow(
+            "> Flowed \n> text.\n>\n>> Older \n>> quote.\n-- \nJane\n",
+            false,
+        );
+        assert_eq!(text, "> Flowed text.\n>\n>> Older quote.\n-- \nJane\n");
+
+        let quoted: Vec<_> = text
+            .lines()
+            .flat_map(|l| quote::quote_line(l, 72))
+            .collect();
+        assert_eq!(
+            quoted,
+            [
+                ">> Flowed text.",
+                ">>",
+                ">>> Older quote.",
+                "> -- ",
+                "> Jane"
+            ]
+        );
+
+        assert_eq!(quote::quote_line("> > foo", 72), [">>> foo"]);
+        assert_eq!(quote::quote_line("", 72), ["> "]);
+        assert_eq!(quote::unflow("a \nb\n", true), "ab\n");
+    }
+
+    #[test]
+    fn diffstat_is_regenerated_from_the_diff() {
+        let body = "Fix it.\n\
+                    \n\
+                    diff --git a/foo.c b/foo.c\n\
+                    --- a/foo.c\n\
+                    +++ b/foo.c\n\
+                    @@ -1,3 +1,3 @@\n\
+                    \x20a\n\
+                    --- b\n\
+                    +++ b\n\
+                    \x20c\n\
+                    diff --git a/include/foo.h b/include/foo.h\n\
+                    --- a/include/foo.h\n\
+                    +++ b/include/foo.h\n\
+                    @@ -1 +1,2 @@\n\
+                    \x20a\n\
+                    +b\n";
+
+        assert_eq!(
+            patch::get_diffstat(body),
+            [
+                " foo.c         | 2 +-",
+                " include/foo.h | 1 +",
+                " 2 files changed, 2 insertions(+), 1 deletion(-)",
+            ]
+        );
+    }
+
+    #[test]
+    fn profile_overrides_the_sender_and_transport() {
+        let mut cfg = GitFile::from_str(
+            "[user]\n\tname = Maxime Ripard\n\temail = mripard@kernel.org\n\
+             [sendemail]\n\tsmtpServer = smtp.kernel.org\n\
+             [acker \"chromium\"]\n\tfrom = Maxime <mripard@chromium.org>\n\
+             \tsmtp = smtp.gmail.com\n\tsignoffName = Max\n",
+        )
+        .unwrap();
+
+        let profile = get_profile(&cfg, Some("chromium")).unwrap();
+        apply_profile(&mut cfg, &profile).unwrap();
+
+        assert_eq!(
+            get_user_mail(&cfg).unwrap().to_string(),
+            "Maxime <mripard@chromium.org>"
+        );
+        assert_eq!(
+            get_config_string(&cfg, "sendemail.smtpserver").as_deref(),
+            Some("smtp.gmail.com")
+        );
+        assert_eq!(
+            get_config_string(&cfg, "acker.signoffName").as_deref(),
+            Some("Max")
+        );
+        assert!(get_profile(&cfg, Some("personal")).is_err());
+    }
+
+    #[test]
+    fn reply_prefixes_are_not_repeated() {
+        for (subject, expected) in [
+            ("[PATCH v3 2/5] drm: fix", "Re: [PATCH v3 2/5] drm: fix"),
+            ("Re: [PATCH v3 2/5] drm: fix", "Re: [PATCH v3 2/5] drm: fix"),
+            ("AW: RE: Antw: [PATCH] drm: fix", "Re: [PATCH] drm: fix"),
+            ("Re[2]: drm: fix", "Re: drm: fix"),
+            ("drm/vc4: hdmi: fix", "Re: drm/vc4: hdmi: fix"),
+        ] {
+            assert_eq!(get_reply_subject(subject), expected);
+        }
+    }
+
+    #[test]
+    fn references_keep_the_whole_thread() {
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    Message-ID: <c@example.com>\r\n\
+                    In-Reply-To: <b@example.com>\r\n\
+                    References: <a@example.com> <b@example.com>\r\n\
+                    \r\n\
+                    Ping?\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+
+        assert_eq!(
+            get_threading(&msg).unwrap(),
+            (
+                String::from("<c@example.com>"),
+                String::from("<a@example.com> <b@example.com> <c@example.com>")
+            )
+        );
+        assert!(make_message_id(&user()).ends_with(".acker@kernel.org>"));
+    }
+
+    #[test]
+    fn stats_count_by_week_and_list() {
+        let sent = |key: &str, day: u64, list: &str| state::Sent {
+            key: key.to_string(),
+            time: day * 24 * 60 * 60,
+            list: list.to_string(),
+        };
+        // 2026-10-12 was a Monday.
+        let history = [
+            sent("acked-by", 20_730, "dri-devel@lists.freedesktop.org"),
+            sent("reviewed-by", 20_738, "dri-devel@lists.freedesktop.org"),
+            sent("acked-by", 20_740, "example.com"),
+            sent("acked-by", 20_744, "dri-devel@lists.freedesktop.org"),
+        ];
+
+        assert_eq!(
+            stats::report(&history, 20_738 * 24 * 60 * 60),
+            "Total: Acked-by: 2, Reviewed-by: 1\n\
+             \n\
+             By week:\n  \
+               2026-10-12: Acked-by: 2, Reviewed-by: 1\n\
+             \n\
+             By list or domain:\n  \
+               dri-devel@lists.freedesktop.org: Acked-by: 1, Reviewed-by: 1\n  \
+               example.com: Acked-by: 1\n"
+        );
+        assert_eq!(stats::parse_since("3months"), Ok(90 * 24 * 60 * 60));
+        assert!(stats::parse_since("soon").is_err());
+    }
+
+    #[test]
+    fn blocked_cc_patterns() {
+        let patterns = [
+            String::from("noreply@*"),
+            String::from("*.bots.example.com"),
+            String::from("lkp@intel.com"),
+        ];
+        let blocked = |a: &str| is_blocked(&patterns, &Mailbox::from_str(a).unwrap());
+
+        assert!(blocked("NoReply@github.com"));
+        assert!(blocked("CI <ci@ci.bots.example.com>"));
+        assert!(blocked("kernel test robot <lkp@intel.com>"));
+        assert!(!blocked("Jane Doe <jane@example.com>"));
+        assert!(!blocked("bots.example.com@example.com"));
+        assert!(glob_match("a*b*c", "axxbyybc"));
+        assert!(!glob_match("a*b?c", "abc"));
+    }
+
+    #[test]
+    fn reply_command_is_the_default() {
+        for cmdline in [
+            &["acker", "-s", "--trailer", "Suggested-by"][..],
+            &["acker", "reply", "-s", "--trailer", "Suggested-by"][..],
+        ] {
+            let cli = Cli::try_parse_ordered(cmdline).unwrap();
+            let args = match &cli.command {
+                Some(Command::Reply(args)) => args,
+                None => &cli.reply,
+                Some(c) => panic!("unexpected command {c:?}"),
+            };
+
+            assert_eq!(
+                get_trailers(args, &user()).to_string(),
+                "Signed-off-by: Maxime Ripard <mripard@kernel.org>\n\
+                 Suggested-by: Maxime Ripard <mripard@kernel.org>\n"
+            );
+        }
+    }
+
+    #[test]
+    fn config_defaults_give_way_to_the_command_line() {
+        let cfg = GitFile::from_str(
+            "[acker]\n\tdryRun = true\n\tautoCc = true\n\tsignature = false\n\tquoteLines = 3\n",
+        )
+        .unwrap();
+
+        let args = Args::try_parse_ordered(["acker", "-a"])
+            .unwrap()
+            .with_config(&cfg)
+            .unwrap();
+        assert!(args.dry && args.auto_cc && args.no_signature);
+        assert_eq!(args.quote_lines, Some(3));
+
+        let args =
+            Args::try_parse_ordered(["acker", "-a", "--no-auto-cc", "--signature", "--quote-all"])
+                .unwrap()
+                .with_config(&cfg)
+                .unwrap();
+        assert!(args.dry && !args.auto_cc && !args.no_signature);
+        assert_eq!(args.quote_lines, None);
+
+        let args = Args::try_parse_ordered(["acker", "-a", "--queue"])
+            .unwrap()
+            .with_config(&cfg)
+            .unwrap();
+        assert!(!args.dry);
+    }
+
+    #[test]
+    fn conflicting_config_defaults_are_refused() {
+        let args = Args::try_parse_ordered(["acker", "-a"]).unwrap();
+
+        for (config, key, other) in [
+            (
+                "attributionOnly = true\n\tquoteAll = true",
+                "acker.quoteAll",
+                "attributionOnly",
+            ),
+            (
+                "attributionOnly = true\n\techoTrailers = true",
+                "acker.echoTrailers",
+                "attributionOnly",
+            ),
+            (
+                "autoCc = true\n\tauthorOnly = true",
+                "acker.authorOnly",
+                "autoCc",
+            ),
+            ("sign = true\n\tsmime = true", "acker.smime", "sign"),
+        ] {
+            let cfg = GitFile::from_str(&format!("[acker]\n\t{config}\n")).unwrap();
+
+            match args.with_config(&cfg) {
+                Err(Error::InvalidConfig(k, v)) => {
+                    assert_eq!(k, key);
+                    assert!(v.contains(other), "{v}");
+                }
+                r => panic!("{config} gave {r:?}"),
+            }
+        }
+
+        // The command line settles it.
+        let cfg =
+            GitFile::from_str("[acker]\n\tattributionOnly = true\n\tquoteAll = true\n").unwrap();
+        let args = Args::try_parse_ordered(["acker", "-a", "--quote-all"])
+            .unwrap()
+            .with_config(&cfg)
+            .unwrap();
+        assert!(args.quote_all && !args.attribution_only);
+    }
+
+    #[test]
+    fn subject_cannot_inject_headers() {
+        isolate();
+
+        let cfg = config();
+        let args = Args::try_parse_from(["acker", "-r"]).unwrap();
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    To: Maxime Ripard <mripard@kernel.org>\r\n\
+                    Subject: =?utf-8?q?[PATCH]_foo=0D=0ABcc:_evil@example.com?=\r\n\
+                    Date: Mon, 12 Oct 2026 10:00:00 +0200\r\n\
+                    Message-ID: <20261012.1234-1-jane@example.com>\r\n\
+                    \r\n\
+                    Fix the frobnicator.\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+        assert!(msg.subject().unwrap().contains('\n'));
+
+        let eml = build_reply(&cfg, &args, &msg, None)
+            .unwrap()
+            .unwrap()
+            .message;
+        let formatted = String::from_utf8(eml.formatted()).unwrap();
+
+        assert!(formatted
+            .lines()
+            .all(|l| !l.to_ascii_lowercase().starts_with("bcc:")));
+        assert!(formatted.contains("Subject: Re: [PATCH] foo Bcc: evil@example.com\r\n"));
+        assert!(eml
+            .envelope()
+            .to()
+            .iter()
+            .all(|a| a.to_string() != "evil@example.com"));
+    }
+
+    #[test]
+    fn folded_subject_is_unfolded() {
+        isolate();
+
+        let cfg = config();
+        let args = Args::try_parse_from(["acker", "-a"]).unwrap();
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    Subject: [PATCH v2 1/3]\r\n\
+                    \tdrm/vc4:  fix the\r\n\
+                    \x20   \t frobnicator\r\n\
+                    Date: Mon, 12 Oct 2026 10:00:00 +0200\r\n\
+                    Message-ID: <20261012.1234-1-jane@example.com>\r\n\
+                    \r\n\
+                    Fix the frobnicator.\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+
+        let eml = build_reply(&cfg, &args, &msg, None)
+            .unwrap()
+            .unwrap()
+            .message;
+        let formatted = String::from_utf8(eml.formatted()).unwrap();
+
+        assert!(formatted.contains("Subject: Re: [PATCH v2 1/3] drm/vc4: fix the frobnicator\r\n"));
+    }
+
+    #[test]
+    fn international_names_round_trip() {
+        isolate();
+
+        let cfg = GitFile::from_bytes_no_includes(
+            b"[user]\n\tname = Ma\xefa Ripard\n\temail = mripard@kernel.org\n",
+            gix_config::file::Metadata::api(),
+            gix_config::file::init::Options::default(),
+        )
+        .unwrap();
+        let args = Args::try_parse_from(["acker", "-a"]).unwrap();
+        let raw = b"From: =?ISO-8859-1?Q?J=E9r=F4me_Pouill=E9?= <jerome@example.com>\r\n\
+                    Cc: =?UTF-8?B?5bGx55Sw5aSq6YOO?= <taro@example.jp>\r\n\
+                    Subject: [PATCH] drm/foo: fix the frobnicator\r\n\
+                    Date: Mon, 12 Oct 2026 10:00:00 +0200\r\n\
+                    Message-ID: <20261012.1234-1-jerome@example.com>\r\n\
+                    \r\n\
+                    Fix the frobnicator.\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+
+        let eml = build_reply(&cfg, &args, &msg, None)
+            .unwrap()
+            .unwrap()
+            .message;
+        let formatted = eml.formatted();
+        let reply = MessageParser::default().parse(&formatted).unwrap();
+
+        let from = reply.from().unwrap().first().unwrap();
+        assert_eq!(from.name(), Some("Ma\u{ef}a Ripard"));
+        let to = reply.to().unwrap().first().unwrap();
+        assert_eq!(to.name(), Some("J\u{e9}r\u{f4}me Pouill\u{e9}"));
+        assert!(reply
+            .cc()
+            .unwrap()
+            .iter()
+            .any(|a| a.name() == Some("\u{5c71}\u{7530}\u{592a}\u{90ce}")));
+        assert!(reply
+            .body_text(0)
+            .unwrap()
+            .contains("J\u{e9}r\u{f4}me Pouill\u{e9} wrote:"));
+    }
+
+    #[test]
+    fn build_errors_name_the_field() {
+        let args = Args::try_parse_ordered(["acker", "-a"]).unwrap();
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    Subject: [PATCH] foo\r\n\
+                    Message-ID: <20261012.1234-1-jane@example.com>\r\n\
+                    \r\n\
+                    Fix the frobnicator.\r\n";
+        let parent = MessageParser::default().parse(&raw[..]).unwrap();
+        let draft = Draft {
+            from: user(),
+            sender: None,
+            subject: String::from("Re: [PATCH] foo"),
+            recipients: Recipients::default(),
+            body: String::from("Acked-by: Maxime Ripard <mripard@kernel.org>\n"),
+        };
+
+        let e = build_message(&config(), &args, &draft, &parent).unwrap_err();
+        assert!(matches!(e, Error::Build("To", _)), "{e:?}");
+    }
+
+    #[test]
+    fn sender_falls_back_to_default_from() {
+        let no_env = |_: &str| None;
+
+        let cfg = GitFile::from_str("[user]\n\tname = Maxime Ripard\n").unwrap();
+        assert!(matches!(
+            get_user_mail_with_env(&cfg, no_env),
+            Err(Error::NoSender)
+        ));
+
+        let cfg = GitFile::from_str(
+            "[user]\n\tname = Maxime Ripard\n[acker]\n\tdefaultFrom = mripard@kernel.org\n",
+        )
+        .unwrap();
+        assert_eq!(get_user_mail_with_env(&cfg, no_env).unwrap(), user());
+
+        let env = |var: &str| (var == "EMAIL").then(|| String::from("maxime@cerno.tech"));
+        assert_eq!(
+            get_user_mail_with_env(&cfg, env).unwrap().to_string(),
+            "Maxime Ripard <maxime@cerno.tech>"
+        );
+    }
+
+    #[test]
+    fn body_ends_with_a_single_line_break() {
+        isolate();
+
+        assert_eq!(
+            normalize_body("Thanks!\nMaxime\n\n \n\n"),
+            "Thanks!\nMaxime\n"
+        );
+        assert_eq!(normalize_body("Thanks!\nMaxime"), "Thanks!\nMaxime\n");
+
+        let cfg = config();
+        let args = Args::try_parse_from(["acker", "-a"]).unwrap();
+        let raw = b"From: Jane Doe <jane@example.com>\r\n\
+                    Subject: [PATCH] foo\r\n\
+                    Date: Mon, 12 Oct 2026 10:00:00 +0200\r\n\
+                    Message-ID: <20261012.1234-1-jane@example.com>\r\n\
+                    \r\n\
+                    Fix the frobnicator.\r\n";
+        let msg = MessageParser::default().parse(&raw[..]).unwrap();
+
+        let eml = build_reply(&cfg, &args, &msg, None)
+            .unwrap()
+            .unwrap()
+            .message;
+        let formatted = String::from_utf8(eml.formatted()).unwrap();
+
+        assert!(formatted.ends_with("\r\nMaxime\r\n"));
+    }
+
+    #[test]
+    fn credentials_are_masked_on_every_connection() {
+        let lines = [
+            ("smtp{id=1}: ", "Wrote: AUTH LOGIN<CRLF>"),
+            ("smtp{id=2}: ", "Wrote: AUTH LOGIN<CRLF>"),
+            ("smtp{id=1}: ", "<< 334 VXNlcm5hbWU6<CRLF>"),
+            ("smtp{id=2}: ", "<< 334 VXNlcm5hbWU6<CRLF>"),
+            ("smtp{id=3}: ", "<< 250 2.1.0 Ok<CRLF>"),
+            ("smtp{id=1}: ", "Wrote: amFuZQ==<CRLF>"),
+            ("smtp{id=1}: ", "<< 334 UGFzc3dvcmQ6<CRLF>"),
+            ("smtp{id=2}: ", "Wrote: bWF4aW1l<CRLF>"),
+            ("smtp{id=1}: ", "Wrote: c2VjcmV0<CRLF>"),
+            ("smtp{id=1}: ", "<< 235 2.7.0 Ok<CRLF>"),
+            ("smtp{id=2}: ", "<< 334 UGFzc3dvcmQ6<CRLF>"),
+            ("smtp{id=2}: ", "Wrote: aHVudGVyMg==<CRLF>"),
+            (
+                "smtp{id=1}: ",
+                "Wrote: MAIL FROM:<mripard@kernel.org><CRLF>",
+            ),
+        ];
+
+        let trace: String = lines
+            .iter()
+            .map(|(span, line)| trace::mask_credentials(&format!("DEBUG {span}{line}\n")))
+            .collect();
+
+        for credential in ["amFuZQ==", "bWF4aW1l", "c2VjcmV0", "aHVudGVyMg=="] {
+            assert!(!trace.contains(credential), "{credential} leaked");
+        }
+        assert!(trace.contains("smtp{id=1}: Wrote: MAIL FROM:<mripard@kernel.org>"));
+    }
+}
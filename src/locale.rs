@@ -0,0 +1,95 @@
+//! Localized greeting and signoff, from the `[acker.locale "xx"]` sections.
+//!
+//! A locale is either given explicitly with `--locale`, or picked from the `domain` values of
+//! the sections matching the domain of the person we reply to.
+
+use gix_config::{file::Section, File as GitFile};
+use lettre::message::Mailbox;
+
+const DEFAULT_SIGNOFF: &str = "Thanks!";
+
+/// The strings surrounding the reply.
+#[derive(Debug)]
+pub(crate) struct Greeting {
+    /// Line to start the reply with, before the attribution.
+    pub(crate) body_prefix: Option<String>,
+    /// Line closing the reply, before our name.
+    pub(crate) signoff: String,
+}
+
+impl Default for Greeting {
+    fn default() -> Self {
+        Greeting {
+            body_prefix: None,
+            signoff: String::from(DEFAULT_SIGNOFF),
+        }
+    }
+}
+
+/// Returns the locale a section is about, if any. git spells `acker.locale.fr.signoff` as
+/// `[acker "locale.fr"]`, but `[acker.locale "fr"]` means the same thing to it.
+fn section_locale(section: &Section<'_>) -> Option<String> {
+    let header = section.header();
+    let subsection = header.subsection_name()?.to_string();
+
+    match header.name().to_string().to_ascii_lowercase().as_str() {
+        "acker.locale" => Some(subsection),
+        "acker" => subsection.strip_prefix("locale.").map(ToString::to_string),
+        _ => None,
+    }
+}
+
+fn locale_sections<'a, 'event>(
+    cfg: &'a GitFile<'event>,
+    locale: &'a str,
+) -> impl Iterator<Item = &'a Section<'event>> + 'a {
+    cfg.sections()
+        .filter(move |s| section_locale(s).as_deref() == Some(locale))
+}
+
+/// Returns the first locale listing the domain of `recipient`.
+fn find_locale(cfg: &GitFile<'_>, recipient: &Mailbox) -> Option<String> {
+    let domain = recipient.email.domain();
+
+    cfg.sections()
+        .find(|s| {
+            section_locale(s).is_some()
+                && s.values("domain")
+                    .iter()
+                    .any(|d| d.to_string().eq_ignore_ascii_case(domain))
+        })
+        .and_then(section_locale)
+}
+
+pub(crate) fn get_greeting(
+    cfg: &GitFile<'_>,
+    locale: Option<&str>,
+    recipient: &Mailbox,
+) -> Greeting {
+    let locale = match locale {
+        Some(l) if locale_sections(cfg, l).next().is_some() => l.to_string(),
+        Some(l) => {
+            eprintln!(
+                "acker: warning: no [acker.locale \"{l}\"] section, using the default greeting"
+            );
+            return Greeting::default();
+        }
+        None => match find_locale(cfg, recipient) {
+            Some(l) => l,
+            None => return Greeting::default(),
+        },
+    };
+
+    // Just like git, the last value wins.
+    let get = |key| {
+        locale_sections(cfg, &locale)
+            .filter_map(|s| s.value(key))
+            .last()
+            .map(|v| v.to_string())
+    };
+
+    Greeting {
+        body_prefix: get("bodyPrefix"),
+        signoff: get("signoff").unwrap_or_else(|| String::from(DEFAULT_SIGNOFF)),
+    }
+}
@@ -0,0 +1,123 @@
+//! Canonical names and addresses of the people, from the `.mailmap` of the repository and the
+//! file of `mailmap.file`, just like git log uses them.
+
+use std::{path::PathBuf, str::FromStr};
+
+use gix_config::{path::interpolate::Context as PathContext, File as GitFile};
+use lettre::{message::Mailbox, Address};
+
+use crate::{git, Error};
+
+#[derive(Debug)]
+struct Entry {
+    name: Option<String>,
+    email: Option<Address>,
+    old_name: Option<String>,
+    old_email: String,
+}
+
+impl Entry {
+    /// Parses a `Proper Name <proper@email> Commit Name <commit@email>` line, where all but the
+    /// last address are optional.
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = Vec::new();
+        let mut rest = line;
+        while let Some((name, tail)) = rest.split_once('<') {
+            let (email, tail) = tail.split_once('>')?;
+            let name = name.trim();
+            parts.push(((!name.is_empty()).then(|| name.to_string()), email.trim()));
+            rest = tail;
+        }
+
+        match parts.as_slice() {
+            [(name, old_email)] => Some(Entry {
+                name: name.clone(),
+                email: None,
+                old_name: None,
+                old_email: old_email.to_lowercase(),
+            }),
+            [(name, email), (old_name, old_email)] => Some(Entry {
+                name: name.clone(),
+                email: Address::from_str(email).ok(),
+                old_name: old_name.clone(),
+                old_email: old_email.to_lowercase(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, mailbox: &Mailbox) -> bool {
+        self.old_email == mailbox.email.to_string().to_lowercase()
+            && self.old_name.as_ref().is_none_or(|old| {
+                mailbox
+                    .name
+                    .as_ref()
+                    .is_some_and(|n| n.eq_ignore_ascii_case(old))
+            })
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Mailmap {
+    entries: Vec<Entry>,
+}
+
+fn read(path: PathBuf, entries: &mut Vec<Entry>) -> Result<(), Error> {
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(Error::Io(path, e)),
+    };
+
+    entries.extend(
+        content
+            .lines()
+            .map(|l| l.split_once('#').map_or(l, |(l, _)| l))
+            .filter_map(Entry::parse),
+    );
+
+    Ok(())
+}
+
+impl Mailmap {
+    /// Loads the `.mailmap` of the repository we run in, if any, and then the one of
+    /// `mailmap.file`.
+    pub(crate) fn load(cfg: &GitFile<'_>) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+
+        if let Ok(top) = git::run(&["rev-parse", "--show-toplevel"]) {
+            read(PathBuf::from(top.trim()).join(".mailmap"), &mut entries)?;
+        }
+
+        if let Some(p) = cfg.path_by_key("mailmap.file") {
+            let path = p
+                .interpolate(PathContext {
+                    home_dir: home::home_dir().as_deref(),
+                    ..Default::default()
+                })
+                .map_err(|e| Error::InvalidConfig("mailmap.file", e.to_string()))?;
+
+            read(path.into_owned(), &mut entries)?;
+        }
+
+        Ok(Mailmap { entries })
+    }
+
+    /// Returns the canonical name and address of `mailbox`.
+    pub(crate) fn map(&self, mailbox: &Mailbox) -> Mailbox {
+        // Like git, the last matching line wins, and the ones with a name are more specific.
+        let Some(entry) = self
+            .entries
+            .iter()
+            .filter(|e| e.matches(mailbox))
+            .max_by_key(|e| e.old_name.is_some())
+        else {
+            return mailbox.clone();
+        };
+
+        Mailbox::new(
+            entry.name.clone().or_else(|| mailbox.name.clone()),
+            entry.email.clone().unwrap_or_else(|| mailbox.email.clone()),
+        )
+    }
+}
@@ -0,0 +1,67 @@
+//! Extra Cc for `--auto-cc`: the maintainers and lists the `get_maintainer.pl` script of the
+//! kernel tree we run in finds for the patch.
+
+use std::{
+    io::Write as _,
+    path::PathBuf,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use gix_config::{path::interpolate::Context as PathContext, File as GitFile};
+use lettre::message::Mailbox;
+
+use crate::{git, Error};
+
+const DEFAULT_SCRIPT: &str = "scripts/get_maintainer.pl";
+
+/// Returns the top of the work tree, where `get_maintainer.pl` wants to run, and the script.
+fn get_script(cfg: &GitFile<'_>) -> Result<(PathBuf, PathBuf), Error> {
+    let top = PathBuf::from(git::run(&["rev-parse", "--show-toplevel"])?.trim());
+
+    let Some(p) = cfg.path_by_key("acker.getMaintainer") else {
+        let script = top.join(DEFAULT_SCRIPT);
+        return Ok((top, script));
+    };
+
+    let path = p
+        .interpolate(PathContext {
+            home_dir: home::home_dir().as_deref(),
+            ..Default::default()
+        })
+        .map_err(|e| Error::InvalidConfig("acker.getMaintainer", e.to_string()))?;
+
+    // A relative path is relative to the tree, like the default one.
+    let script = top.join(path);
+    Ok((top, script))
+}
+
+/// Returns the people and lists `get_maintainer.pl` finds for `patch`.
+pub(crate) fn get(cfg: &GitFile<'_>, patch: &str) -> Result<Vec<Mailbox>, Error> {
+    let (top, script) = get_script(cfg)?;
+    let io_err = |e| Error::Io(script.clone(), e);
+
+    // Without the roles, each line is only the name and the address. Going through the git
+    // history of the files is slow and finds people that aren't maintainers anymore.
+    let mut child = Command::new(&script)
+        .args(["--no-rolestats", "--no-git-fallback"])
+        .current_dir(&top)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(io_err)?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(patch.as_bytes()).map_err(io_err)?;
+    }
+
+    let output = child.wait_with_output().map_err(io_err)?;
+    if !output.status.success() {
+        return Err(io_err(std::io::Error::other(output.status.to_string())));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| Mailbox::from_str(l.trim()).ok())
+        .collect())
+}
@@ -0,0 +1,108 @@
+//! Record of the trailers we gave, as git notes in `refs/notes/acker` on the commits the patches
+//! landed as.
+//!
+//! The commits are found from the `Link:` or `Message-ID:` trailers pointing at the patch, like
+//! b4 and most maintainer scripts add when applying them.
+
+use std::{fmt::Write as _, process::ExitCode};
+
+use gix_config::File as GitFile;
+
+use crate::{get_archive_link, git, state, Error, TrailerStyle};
+
+const NOTES_REF: &str = "acker";
+
+/// Returns the Message-ID a trailer line of a commit message points at, if any.
+fn get_message_id(line: &str) -> Option<String> {
+    let (key, value) = line.split_once(':')?;
+    let value = value.trim();
+
+    if key.trim().eq_ignore_ascii_case("Message-ID") {
+        let id = value.trim_start_matches('<').trim_end_matches('>');
+        return id.contains('@').then(|| id.to_string());
+    }
+
+    if !key.trim().eq_ignore_ascii_case("Link") {
+        return None;
+    }
+
+    // Archives such as lore.kernel.org make the Message-ID the last part of the URL.
+    let id = percent_decode(value.trim_end_matches('/').rsplit('/').next()?);
+    id.contains('@').then_some(id)
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// `acker notes`: adds a note to the commits of `revision` whose patch we gave trailers to,
+/// pointing at the patch with a `Link:` to the archive if `link` is set.
+pub(crate) fn record(cfg: &GitFile<'_>, revision: &str, link: bool) -> Result<ExitCode, Error> {
+    let log = state::SentLog::load()?;
+    let commits = git::run(&[
+        "log",
+        "--format=%H%n%B%x00",
+        "--max-count",
+        git::MAX_COMMITS,
+        revision,
+        "--",
+    ])?;
+
+    let mut count = 0;
+    for commit in commits.split('\0') {
+        let mut lines = commit.trim_start().lines();
+        let Some(hash) = lines.next() else {
+            continue;
+        };
+
+        let mut note = String::new();
+        for id in lines.filter_map(get_message_id) {
+            let given = log.given(&id);
+            if given.is_empty() {
+                continue;
+            }
+
+            if link {
+                let _ = writeln!(note, "Link: {}", get_archive_link(cfg, &id));
+            } else {
+                let _ = writeln!(note, "Message-ID: <{id}>");
+            }
+            for (key, address) in given {
+                let _ = writeln!(note, "{}: {address}", TrailerStyle::Canonical.apply(key));
+            }
+        }
+
+        if note.is_empty() {
+            continue;
+        }
+
+        git::run(&["notes", "--ref", NOTES_REF, "add", "-f", "-m", &note, hash])?;
+        count += 1;
+    }
+
+    eprintln!("acker: recorded the trailers given to {count} commit(s) in refs/notes/{NOTES_REF}");
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,101 @@
+//! notmuch integration: the sent reply gets indexed, and the original tagged.
+//!
+//! `acker.notmuchInsert` adds the reply to the database with `notmuch insert`, in the folder of
+//! `acker.notmuchFolder`. `acker.notmuchTags` is the list of tag changes, such as `+acked -todo`,
+//! to apply to the original message.
+
+use std::{
+    io::Write as _,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use gix_config::File as GitFile;
+use lettre::Message;
+
+use crate::{get_config_bool, get_config_string, get_config_strings, Error};
+
+const NOTMUCH: &str = "notmuch";
+
+#[derive(Debug)]
+pub(crate) struct Notmuch {
+    insert: bool,
+    folder: Option<String>,
+    tags: Vec<String>,
+}
+
+fn run(mut cmd: Command, stdin: Option<&[u8]>) -> Result<(), Error> {
+    let io_err = |e| Error::Io(PathBuf::from(NOTMUCH), e);
+
+    let mut child = cmd
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .spawn()
+        .map_err(io_err)?;
+
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(input).map_err(io_err)?;
+    }
+
+    let status = child.wait().map_err(io_err)?;
+    if !status.success() {
+        return Err(Error::Notmuch(status.to_string()));
+    }
+
+    Ok(())
+}
+
+impl Notmuch {
+    /// Returns what to do with notmuch, if anything.
+    pub(crate) fn from_config(cfg: &GitFile<'_>) -> Result<Option<Self>, Error> {
+        let insert = get_config_bool(cfg, "acker.notmuchInsert")?.unwrap_or(false);
+        let tags: Vec<_> = get_config_strings(cfg, "acker.notmuchTags")
+            .iter()
+            .flat_map(|t| t.split_whitespace())
+            .map(String::from)
+            .collect();
+
+        if let Some(tag) = tags.iter().find(|t| !t.starts_with(['+', '-'])) {
+            return Err(Error::InvalidConfig("acker.notmuchTags", tag.clone()));
+        }
+
+        if !insert && tags.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Notmuch {
+            insert,
+            folder: get_config_string(cfg, "acker.notmuchFolder"),
+            tags,
+        }))
+    }
+
+    /// Indexes `reply`, and tags the message with the `message_id` it replies to.
+    pub(crate) fn sent(&self, message_id: Option<&str>, reply: &Message) -> Result<(), Error> {
+        if self.insert {
+            let mut cmd = Command::new(NOTMUCH);
+            cmd.arg("insert");
+            if let Some(folder) = &self.folder {
+                cmd.arg(format!("--folder={folder}")).arg("--create-folder");
+            }
+
+            let content = String::from_utf8_lossy(&reply.formatted()).replace("\r\n", "\n");
+            run(cmd, Some(content.as_bytes()))?;
+        }
+
+        if let (Some(id), false) = (message_id, self.tags.is_empty()) {
+            let mut cmd = Command::new(NOTMUCH);
+            cmd.arg("tag")
+                .args(&self.tags)
+                .arg("--")
+                .arg(format!("id:\"{}\"", id.replace('"', "\"\"")));
+
+            run(cmd, None)?;
+        }
+
+        Ok(())
+    }
+}
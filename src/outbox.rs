@@ -0,0 +1,104 @@
+//! Outbox for the replies written offline: `--queue` stores them, `acker send-queue` sends them.
+//!
+//! Each reply is stored as an `.eml` file along with its envelope, so that the Bcc recipients
+//! don't get lost on the way.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use gix_config::{path::interpolate::Context as PathContext, File as GitFile};
+use lettre::{FileTransport, Message, Transport};
+
+use crate::{batch, Error, MailTransport};
+
+fn outbox_dir(cfg: &GitFile<'_>) -> Result<PathBuf, Error> {
+    if let Some(p) = cfg.path_by_key("acker.outbox") {
+        return p
+            .interpolate(PathContext {
+                home_dir: home::home_dir().as_deref(),
+                ..Default::default()
+            })
+            .map(std::borrow::Cow::into_owned)
+            .map_err(|e| Error::InvalidConfig("acker.outbox", e.to_string()));
+    }
+
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| home::home_dir().map(|h| h.join(".local").join("share")))
+        .map(|d| d.join("acker").join("outbox"))
+        .ok_or(Error::MissingConfig("HOME"))
+}
+
+fn outbox(cfg: &GitFile<'_>) -> Result<(PathBuf, FileTransport), Error> {
+    let dir = outbox_dir(cfg)?;
+    std::fs::create_dir_all(&dir).map_err(|e| Error::Io(dir.clone(), e))?;
+
+    let transport = FileTransport::with_envelope(&dir);
+    Ok((dir, transport))
+}
+
+/// Stores `eml` in the outbox, and returns the path it got there.
+pub(crate) fn queue(cfg: &GitFile<'_>, eml: &Message) -> Result<PathBuf, Error> {
+    let (dir, transport) = outbox(cfg)?;
+
+    let id = transport
+        .send(eml)
+        .map_err(|e| Error::Io(dir.clone(), std::io::Error::other(e)))?;
+    Ok(dir.join(id).with_extension("eml"))
+}
+
+/// `acker send-queue`: sends all the queued replies, in the order they were queued.
+pub(crate) fn flush(cfg: &GitFile<'_>, transport: &MailTransport) -> Result<ExitCode, Error> {
+    let (dir, outbox) = outbox(cfg)?;
+
+    let mut queued = std::fs::read_dir(&dir)
+        .and_then(|entries| {
+            entries
+                .map(|e| {
+                    let e = e?;
+                    Ok((e.metadata()?.modified()?, e.path()))
+                })
+                .collect::<Result<Vec<_>, std::io::Error>>()
+        })
+        .map_err(|e| Error::Io(dir.clone(), e))?;
+    queued.retain(|(_, p)| p.extension().is_some_and(|e| e == "eml"));
+    queued.sort();
+
+    let mut emails = Vec::new();
+    for (_, path) in &queued {
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+
+        let email = outbox
+            .read(id)
+            .map_err(|e| Error::Io(path.clone(), std::io::Error::other(e)))?;
+        emails.push((path, email));
+    }
+
+    let (paths, emails): (Vec<_>, Vec<_>) = emails.into_iter().unzip();
+    let results = batch::send_all(transport, &emails, batch::get_concurrency(cfg, transport)?);
+
+    let mut failed = 0;
+    for (path, result) in paths.into_iter().zip(results) {
+        if let Err(e) = result {
+            eprintln!("acker: warning: couldn't send {}: {e}", path.display());
+            failed += 1;
+            continue;
+        }
+
+        for file in [path.clone(), path.with_extension("json")] {
+            std::fs::remove_file(&file).map_err(|e| Error::Io(file, e))?;
+        }
+    }
+
+    if failed > 0 {
+        return Err(Error::Send(format!(
+            "{failed} of the {} queued replies are still in {}",
+            queued.len(),
+            dir.display()
+        )));
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,90 @@
+//! The ways to print the replies with `--dry-run`.
+
+use std::fmt::Write as _;
+
+use clap::ValueEnum;
+use lettre::message::Mailbox;
+use serde_json::json;
+
+use crate::Reply;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum OutputFormat {
+    /// The message, as it would be sent
+    #[default]
+    Raw,
+    /// An overview of the recipients and the trailers
+    Summary,
+    /// The parts of the reply, as a JSON object per line
+    Json,
+}
+
+fn join(mailboxes: &[Mailbox]) -> String {
+    mailboxes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn summary(reply: &Reply) -> String {
+    let draft = &reply.draft;
+    let mut summary = format!("Subject: {}\nFrom: {}\n", draft.subject, draft.from);
+    if let Some(sender) = &draft.sender {
+        let _ = writeln!(summary, "Sender: {sender}");
+    }
+
+    for (field, list) in [
+        ("To", &draft.recipients.to),
+        ("Cc", &draft.recipients.cc),
+        ("Bcc", &draft.recipients.bcc),
+    ] {
+        if !list.is_empty() {
+            let _ = writeln!(summary, "{field}: {}", join(list));
+        }
+    }
+
+    if reply.author_only {
+        summary.push_str("Private: only to the author, the Cc were left out\n");
+    }
+
+    summary.push_str("Trailers:\n");
+    for trailer in &reply.trailers {
+        let _ = writeln!(summary, "  {trailer}");
+    }
+    summary.push('\n');
+
+    summary
+}
+
+fn to_json(reply: &Reply) -> String {
+    let draft = &reply.draft;
+    let strings = |list: &[Mailbox]| list.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+    let object = json!({
+        "from": draft.from.to_string(),
+        "sender": draft.sender.as_ref().map(ToString::to_string),
+        "to": strings(&draft.recipients.to),
+        "cc": strings(&draft.recipients.cc),
+        "bcc": strings(&draft.recipients.bcc),
+        "subject": draft.subject,
+        "author_only": reply.author_only,
+        "trailers": reply
+            .trailers
+            .iter()
+            .map(|t| json!({ "key": t.key, "value": t.value, "comment": t.comment }))
+            .collect::<Vec<_>>(),
+        "body": draft.body,
+    });
+
+    format!("{object}\n")
+}
+
+/// Returns `reply` printed in `format`.
+pub(crate) fn format(format: OutputFormat, reply: &Reply) -> Vec<u8> {
+    match format {
+        OutputFormat::Raw => reply.message.formatted(),
+        OutputFormat::Summary => summary(reply).into_bytes(),
+        OutputFormat::Json => to_json(reply).into_bytes(),
+    }
+}
@@ -0,0 +1,359 @@
+//! Sanity checks of the patches we're about to put a tag on, up to building and testing them
+//! in the current git work tree.
+
+use std::{
+    fmt::Write as _,
+    io::Write as _,
+    path::PathBuf,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use lettre::message::Mailbox;
+
+use crate::{git::GIT, split_trailer_comment, Error};
+
+/// The trailers that give the name and address of someone.
+const PERSON_KEYS: [&str; 8] = [
+    "Signed-off-by",
+    "Acked-by",
+    "Reviewed-by",
+    "Tested-by",
+    "Reported-by",
+    "Suggested-by",
+    "Co-developed-by",
+    "Cc",
+];
+
+/// Returns whether `subject` and `body` look like a patch rather than a discussion.
+fn is_patch(subject: &str, body: &str) -> bool {
+    let prefix = subject
+        .split_once('[')
+        .and_then(|(_, p)| p.split_once(']'))
+        .map_or("", |(p, _)| p);
+
+    prefix.to_ascii_uppercase().contains("PATCH") || has_diff(body)
+}
+
+/// Returns whether `line` starts the diff of a patch.
+pub(crate) fn is_diff_start(line: &str) -> bool {
+    line.starts_with("diff --git ") || line.starts_with("--- a/")
+}
+
+/// Returns whether `body` carries a diff to apply.
+pub(crate) fn has_diff(body: &str) -> bool {
+    body.lines().any(is_diff_start)
+}
+
+/// Returns the problems found in the patch in `body`, sent with `subject` and written by
+/// `author`, as messages to show to the user.
+pub(crate) fn check(subject: &str, body: &str, author: &Mailbox) -> Vec<String> {
+    if !is_patch(subject, body) {
+        return Vec::new();
+    }
+
+    let mut problems = Vec::new();
+    let commit_message = body
+        .lines()
+        .take_while(|l| *l != "---" && !is_diff_start(l));
+    let signoffs: Vec<_> = commit_message
+        .filter_map(|l| l.split_once(':'))
+        .filter(|(key, _)| key.trim().eq_ignore_ascii_case("Signed-off-by"))
+        .map(|(_, value)| value.trim())
+        .collect();
+
+    match signoffs.first() {
+        None => problems.push(String::from("the patch has no Signed-off-by line")),
+        Some(first) => {
+            // git puts the author's Signed-off-by first, anything else usually means the
+            // authorship got lost on the way.
+            let address = author.email.to_string();
+            let same = Mailbox::from_str(first)
+                .is_ok_and(|m| m.email.to_string().eq_ignore_ascii_case(&address));
+            if !same {
+                problems.push(format!(
+                    "the patch is written by {author}, but its first Signed-off-by is {first}"
+                ));
+            }
+        }
+    }
+
+    // A tag nobody can be reached at is usually a botched copy and paste.
+    let trailers = body
+        .lines()
+        .take_while(|l| *l != "---" && !is_diff_start(l))
+        .filter_map(|l| l.split_once(':'))
+        .filter(|(key, _)| {
+            PERSON_KEYS
+                .iter()
+                .any(|k| key.trim().eq_ignore_ascii_case(k))
+        });
+    for (key, value) in trailers {
+        if Mailbox::from_str(split_trailer_comment(value.trim()).0).is_err() {
+            problems.push(format!(
+                "the {} line of the patch, {:?}, isn't a valid address",
+                key.trim(),
+                value.trim()
+            ));
+        }
+    }
+
+    if !body
+        .lines()
+        .any(|l| l.starts_with("diff --git ") || l.starts_with("@@ "))
+    {
+        problems.push(String::from("the patch doesn't change anything"));
+    }
+
+    problems
+}
+
+fn git_apply(flags: &[&str], patch: &str) -> Result<bool, Error> {
+    let io_err = |e| Error::Io(PathBuf::from(GIT), e);
+
+    let mut child = Command::new(GIT)
+        .arg("apply")
+        .args(flags)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(io_err)?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(patch.as_bytes()).map_err(io_err)?;
+    }
+
+    Ok(child.wait().map_err(io_err)?.success())
+}
+
+/// The patches applied to the work tree to test them, taken back off once we're done.
+#[derive(Debug)]
+pub(crate) struct Worktree {
+    command: String,
+    applied: Vec<String>,
+}
+
+impl Worktree {
+    pub(crate) fn new(command: String) -> Self {
+        Worktree {
+            command,
+            applied: Vec::new(),
+        }
+    }
+
+    /// Applies `patch` on top of the ones tested before, and runs the test command on the result.
+    pub(crate) fn test(&mut self, subject: &str, patch: &str) -> Result<(), Error> {
+        if !git_apply(&[], patch)? {
+            return Err(Error::PatchDoesntApply(subject.to_string()));
+        }
+        self.applied.push(patch.to_string());
+
+        // The build output is for the user, stdout is ours for --dry-run.
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::null())
+            .stdout(std::io::stderr())
+            .status()
+            .map_err(|e| Error::Io(PathBuf::from("sh"), e))?;
+
+        if !status.success() {
+            return Err(Error::TestFailed(self.command.clone(), status.to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        for patch in self.applied.iter().rev() {
+            if !git_apply(&["-R"], patch).unwrap_or(false) {
+                eprintln!(
+                    "acker: warning: couldn't take the tested patches back off the work tree"
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Checks that the patches of a series apply to the work tree, each on top of the previous ones,
+/// without touching it.
+#[derive(Debug, Default)]
+pub(crate) struct ApplyCheck {
+    series: String,
+}
+
+impl ApplyCheck {
+    /// Returns whether `patch` applies after the ones checked before.
+    pub(crate) fn check(&mut self, patch: &str) -> Result<bool, Error> {
+        let len = self.series.len();
+        self.series.push_str(patch);
+        self.series.push('\n');
+
+        let applies = git_apply(&["--check"], &self.series)?;
+        if !applies {
+            // Don't make the rest of the series fail as well.
+            self.series.truncate(len);
+        }
+
+        Ok(applies)
+    }
+}
+
+/// How wide the `+++--` graph of the diffstats we regenerate gets at most.
+const DIFFSTAT_GRAPH_WIDTH: usize = 40;
+
+fn is_diffstat_line(line: &str) -> bool {
+    let Some((path, change)) = line.split_once(" | ") else {
+        return false;
+    };
+
+    line.starts_with(' ')
+        && !path.trim().is_empty()
+        && change
+            .trim()
+            .starts_with(|c: char| c.is_ascii_digit() || c == 'B')
+}
+
+fn is_diffstat_summary(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with(|c: char| c.is_ascii_digit())
+        && (line.contains(" file changed") || line.contains(" files changed"))
+}
+
+/// Returns the diffstat git format-patch put between the `---` line and the diff of `body`.
+fn find_diffstat(body: &str) -> Vec<String> {
+    let section = body
+        .lines()
+        .skip_while(|l| *l != "---")
+        .skip(1)
+        .take_while(|l| !is_diff_start(l));
+
+    let lines: Vec<_> = section
+        .filter(|l| is_diffstat_line(l) || is_diffstat_summary(l))
+        .map(String::from)
+        .collect();
+
+    if !lines.last().is_some_and(|l| is_diffstat_summary(l)) {
+        return Vec::new();
+    }
+
+    lines
+}
+
+/// Counts the lines added and removed for each file of the diff in `body`.
+fn count_changes(body: &str) -> Vec<(String, usize, usize)> {
+    let mut files: Vec<(String, usize, usize)> = Vec::new();
+    let (mut old, mut new) = (0_usize, 0_usize);
+
+    for line in body.lines() {
+        // Inside a hunk, its header tells how many lines are left, whatever they start with.
+        if old > 0 || new > 0 {
+            let Some(file) = files.last_mut() else {
+                break;
+            };
+
+            match line.chars().next() {
+                Some('+') => {
+                    file.1 += 1;
+                    new = new.saturating_sub(1);
+                }
+                Some('-') => {
+                    file.2 += 1;
+                    old = old.saturating_sub(1);
+                }
+                Some('\\') => {}
+                _ => {
+                    old = old.saturating_sub(1);
+                    new = new.saturating_sub(1);
+                }
+            }
+            continue;
+        }
+
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            let path = paths.rsplit_once(" b/").map_or(paths, |(_, p)| p);
+            files.push((path.to_string(), 0, 0));
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            let path = path.strip_prefix("b/").unwrap_or(path);
+            // Plain diffs don't have a diff --git line to start each file.
+            if path != "/dev/null" && files.last().is_none_or(|f| f.0 != path) {
+                files.push((path.to_string(), 0, 0));
+            }
+        } else if let Some(range) = line.strip_prefix("@@ -") {
+            let count = |r: &str| {
+                r.split_once(',')
+                    .map_or(Some(1), |(_, n)| n.parse::<usize>().ok())
+                    .unwrap_or(0)
+            };
+            let mut ranges = range.split(' ');
+            old = ranges.next().map_or(0, count);
+            new = ranges
+                .next()
+                .and_then(|r| r.strip_prefix('+'))
+                .map_or(0, count);
+        }
+    }
+
+    files
+}
+
+fn plural(count: usize, singular: &str, plural: &str) -> String {
+    format!("{count} {}", if count == 1 { singular } else { plural })
+}
+
+/// Regenerates the diffstat of the diff in `body`, like git diff --stat would.
+fn make_diffstat(body: &str) -> Vec<String> {
+    let files = count_changes(body);
+    if files.is_empty() {
+        return Vec::new();
+    }
+
+    let path_width = files.iter().map(|f| f.0.chars().count()).max().unwrap_or(0);
+    let most = files.iter().map(|f| f.1 + f.2).max().unwrap_or(0);
+    let count_width = most.to_string().len();
+
+    let mut lines: Vec<_> = files
+        .iter()
+        .map(|(path, added, removed)| {
+            let (mut plus, mut minus) = (*added, *removed);
+            if most > DIFFSTAT_GRAPH_WIDTH {
+                plus = (plus * DIFFSTAT_GRAPH_WIDTH).div_ceil(most);
+                minus = (minus * DIFFSTAT_GRAPH_WIDTH).div_ceil(most);
+            }
+
+            format!(
+                " {path:<path_width$} | {:>count_width$} {}{}",
+                added + removed,
+                "+".repeat(plus),
+                "-".repeat(minus)
+            )
+        })
+        .collect();
+
+    let added: usize = files.iter().map(|f| f.1).sum();
+    let removed: usize = files.iter().map(|f| f.2).sum();
+    let mut summary = format!(" {}", plural(files.len(), "file changed", "files changed"));
+    if added > 0 {
+        let _ = write!(summary, ", {}(+)", plural(added, "insertion", "insertions"));
+    }
+    if removed > 0 {
+        let _ = write!(summary, ", {}(-)", plural(removed, "deletion", "deletions"));
+    }
+    lines.push(summary);
+
+    lines
+}
+
+/// Returns the diffstat of the patch in `body`, the one git format-patch wrote if it's there,
+/// or one regenerated from its diff otherwise.
+pub(crate) fn get_diffstat(body: &str) -> Vec<String> {
+    let diffstat = find_diffstat(body);
+    if !diffstat.is_empty() {
+        return diffstat;
+    }
+
+    make_diffstat(body)
+}
@@ -0,0 +1,132 @@
+//! Update of the patches in Patchwork once we replied to them.
+//!
+//! The instance is set with `acker.patchworkUrl`, and its API token with `acker.patchworkToken`.
+//! Patchwork then gets the state of `acker.patchworkState`, and a check listing our trailers if
+//! `acker.patchworkCheck` is set.
+
+use std::fmt::Write;
+
+use gix_config::File as GitFile;
+use serde_json::Value;
+use ureq::{json, Agent};
+
+use crate::{
+    fetch::{describe_error, escape_message_id, http_agent},
+    get_config_bool, get_config_string, Error, TrailerSet,
+};
+
+/// The context of our checks, telling them apart from the CI ones.
+const CHECK_CONTEXT: &str = "acker";
+
+#[derive(Debug)]
+pub(crate) struct Patchwork {
+    agent: Agent,
+    url: String,
+    token: String,
+    project: Option<String>,
+    state: Option<String>,
+    check: bool,
+}
+
+impl Patchwork {
+    /// Returns the Patchwork instance to update, if one is configured.
+    pub(crate) fn from_config(cfg: &GitFile<'_>) -> Result<Option<Self>, Error> {
+        let Some(url) = get_config_string(cfg, "acker.patchworkUrl") else {
+            return Ok(None);
+        };
+
+        let token = get_config_string(cfg, "acker.patchworkToken")
+            .ok_or(Error::MissingConfig("acker.patchworkToken"))?;
+        let agent = http_agent().map_err(|e| Error::Patchwork(url.clone(), e.to_string()))?;
+
+        Ok(Some(Patchwork {
+            agent,
+            url: url.trim_end_matches('/').to_string(),
+            token,
+            project: get_config_string(cfg, "acker.patchworkProject"),
+            state: get_config_string(cfg, "acker.patchworkState"),
+            check: get_config_bool(cfg, "acker.patchworkCheck")?.unwrap_or(false),
+        }))
+    }
+
+    fn error(&self, e: &dyn std::fmt::Display) -> Error {
+        Error::Patchwork(self.url.clone(), e.to_string())
+    }
+
+    /// Returns the API URLs of the patches with this Message-ID. There can be several of them, if
+    /// the patch was sent to several projects.
+    fn find_patches(&self, message_id: &str) -> Result<Vec<String>, Error> {
+        let mut url = format!(
+            "{}/api/patches/?msgid={}",
+            self.url,
+            escape_message_id(message_id)
+        );
+        if let Some(project) = &self.project {
+            let _ = write!(url, "&project={}", escape_message_id(project));
+        }
+
+        let patches: Value = self
+            .agent
+            .get(&url)
+            .call()
+            .map_err(|e| self.error(&describe_error(e)))?
+            .into_json()
+            .map_err(|e| self.error(&e))?;
+
+        Ok(patches
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|p| p["url"].as_str())
+            .map(ToString::to_string)
+            .collect())
+    }
+
+    fn send(&self, method: &str, url: &str, body: &Value) -> Result<(), Error> {
+        self.agent
+            .request(method, url)
+            .set("Authorization", &format!("Token {}", self.token))
+            .send_json(body)
+            .map_err(|e| self.error(&describe_error(e)))?;
+
+        Ok(())
+    }
+
+    /// Updates the patches we replied to with `trailers`.
+    pub(crate) fn update(&self, message_id: &str, trailers: &TrailerSet) -> Result<(), Error> {
+        if self.state.is_none() && !self.check {
+            return Ok(());
+        }
+
+        let patches = self.find_patches(message_id)?;
+        if patches.is_empty() {
+            return Err(self.error(&format!("no patch with Message-ID <{message_id}>")));
+        }
+
+        for patch in patches {
+            if let Some(state) = &self.state {
+                self.send("PATCH", &patch, &json!({ "state": state }))?;
+            }
+
+            if self.check {
+                let description = trailers
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                self.send(
+                    "POST",
+                    &format!("{}/checks/", patch.trim_end_matches('/')),
+                    &json!({
+                        "state": "success",
+                        "context": CHECK_CONTEXT,
+                        "description": description,
+                    }),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
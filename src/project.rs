@@ -0,0 +1,120 @@
+//! Configuration of the project acker runs in, on top of the global one: the `.git/config` of
+//! the repository, and the `.acker-config` committed at its top, in the git configuration
+//! syntax, for the defaults the project shares with everyone replying to its patches.
+//!
+//! The committed file comes from whoever pushed to the repository, so it only gets to set the
+//! keys telling how to reply, and not the ones running programs or reading files.
+
+use std::path::PathBuf;
+
+use gix_config::{File as GitFile, Source};
+use tracing::info;
+
+use crate::{decode_config_value, defaults, git, Error};
+
+/// The file of the project defaults, at the top of the work tree.
+pub(crate) const FILE: &str = ".acker-config";
+
+/// The keys of [`FILE`] besides the flags, the cc and quoting defaults a project could want.
+const SHARED_KEYS: [&str; 19] = [
+    "acker.cc",
+    "acker.bcc",
+    "acker.ccBlock",
+    "acker.listAddresses",
+    "acker.listsInTo",
+    "acker.maxCc",
+    "acker.maxRecipients",
+    "acker.warnRecipients",
+    "acker.quoteLines",
+    "acker.quoteWidth",
+    "acker.maxQuoteBytes",
+    "acker.replyCount",
+    "acker.wrapWidth",
+    "acker.trailerStyle",
+    "acker.truncationMarker",
+    "acker.dateFmt",
+    "acker.attributionTimezone",
+    "acker.linkbase",
+    "acker.checkFromDomain",
+];
+
+/// Returns whether [`FILE`] can set `key`. `acker.autoCc` runs a script of the tree, so it's
+/// left to the people who trust it.
+fn is_shared_key(key: &str) -> bool {
+    SHARED_KEYS.iter().any(|k| k.eq_ignore_ascii_case(key))
+        || (defaults::is_flag_key(key) && !key.eq_ignore_ascii_case("acker.autoCc"))
+}
+
+fn read(path: PathBuf, source: Source) -> Result<GitFile<'static>, Error> {
+    GitFile::from_path_no_includes(path.clone(), source)
+        .map_err(|e| Error::Io(path, std::io::Error::other(e)))
+}
+
+/// Appends the keys of [`FILE`] that it can set to `cfg`, and warns about the others.
+fn apply_shared(cfg: &mut GitFile<'static>, path: PathBuf) -> Result<(), Error> {
+    let shared = read(path, Source::Worktree)?;
+
+    let mut values = Vec::new();
+    for section in shared.sections() {
+        let header = section.header();
+        for key in section.keys() {
+            let name = match header.subsection_name() {
+                Some(sub) => format!("{}.{sub}.{key}", header.name()),
+                None => format!("{}.{key}", header.name()),
+            };
+
+            if header.subsection_name().is_some() || !is_shared_key(&name) {
+                eprintln!("acker: warning: {FILE} can't set {name}, ignoring it");
+                continue;
+            }
+
+            for value in section.values(key.as_ref()) {
+                values.push((key.clone(), decode_config_value(&value)));
+            }
+        }
+    }
+
+    if values.is_empty() {
+        return Ok(());
+    }
+
+    let mut section = cfg
+        .new_section("acker", None)
+        .map_err(|e| Error::InvalidConfig("acker", e.to_string()))?;
+    for (key, value) in values {
+        section.push(key, Some(value.as_str().into()));
+    }
+
+    Ok(())
+}
+
+/// Adds the configuration of the repository acker runs in, if any, to `cfg`: the keys of
+/// [`FILE`] override the global ones, and the ones of `.git/config` everything else.
+pub(crate) fn apply(cfg: &mut GitFile<'static>) -> Result<(), Error> {
+    let Ok(dirs) = git::run(&["rev-parse", "--show-toplevel", "--git-common-dir"]) else {
+        return Ok(());
+    };
+
+    let mut lines = dirs.lines();
+    let (Some(top), Some(git_dir)) = (lines.next(), lines.next()) else {
+        return Ok(());
+    };
+
+    let shared = PathBuf::from(top).join(FILE);
+    if shared.exists() {
+        info!("reading the project defaults of {}", shared.display());
+        apply_shared(cfg, shared)?;
+    }
+
+    // The common directory is relative to where we are, unless we're elsewhere in the tree.
+    let local = std::env::current_dir()
+        .map_err(|e| Error::Io(PathBuf::from("."), e))?
+        .join(git_dir)
+        .join("config");
+    if local.exists() {
+        info!("reading the configuration of {}", local.display());
+        cfg.append(read(local, Source::Local)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Questions asked to the user on the terminal.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use gix_config::File as GitFile;
+
+use crate::{editor, Draft, Error};
+
+fn stdin_error(e: std::io::Error) -> Error {
+    Error::Io(PathBuf::from("<stdin>"), e)
+}
+
+/// Where the user's terminal is, when stdin isn't it.
+const TTY: &str = "/dev/tty";
+
+/// Opens the user's terminal when stdin isn't it, most likely because it held the message we
+/// reply to. Returns `None` when stdin is the terminal.
+pub(crate) fn open_tty() -> Result<Option<File>, Error> {
+    if std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+
+    File::open(TTY).map(Some).map_err(Error::NoTerminal)
+}
+
+pub(crate) fn prompt(question: &str, default: Option<&str>) -> Result<String, Error> {
+    let mut stdout = std::io::stdout();
+
+    match default {
+        Some(d) if !d.is_empty() => print!("{question} [{d}]: "),
+        _ => print!("{question}: "),
+    }
+    stdout.flush().map_err(stdin_error)?;
+
+    let mut answer = String::new();
+    let read = match open_tty()? {
+        Some(tty) => BufReader::new(tty)
+            .read_line(&mut answer)
+            .map_err(|e| Error::Io(PathBuf::from(TTY), e))?,
+        None => std::io::stdin()
+            .lock()
+            .read_line(&mut answer)
+            .map_err(stdin_error)?,
+    };
+
+    // Nobody is there to answer, which is no reason to go on with the defaults.
+    if read == 0 {
+        return Err(Error::Aborted);
+    }
+
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(default.unwrap_or_default().to_string());
+    }
+
+    Ok(answer.to_string())
+}
+
+pub(crate) fn confirm(question: &str) -> Result<bool, Error> {
+    let answer = prompt(&format!("{question} [y/N]"), None)?;
+
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Shows the reply and asks whether to send it. Returns the reply to send, possibly edited, or
+/// `None` to skip it.
+pub(crate) fn confirm_reply(cfg: &GitFile<'_>, mut draft: Draft) -> Result<Option<Draft>, Error> {
+    loop {
+        println!("{}", editor::format_draft(&draft));
+
+        let answer = prompt("Send this reply? ([y]es, [n]o, [e]dit, [q]uit)", Some("y"))?;
+        match answer.to_lowercase().as_str() {
+            "y" | "yes" => return Ok(Some(draft)),
+            "n" | "no" => return Ok(None),
+            "e" | "edit" => draft = editor::edit_reply(cfg, &draft)?,
+            "q" | "quit" => return Err(Error::Aborted),
+            _ => println!("Please answer y, n, e or q"),
+        }
+    }
+}
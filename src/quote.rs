@@ -0,0 +1,104 @@
+//! Quoting of the original message: format=flowed bodies get their paragraphs back, and the
+//! lines that are already quoted get one more level rather than `> > ` markers.
+
+use mail_parser::{MessagePart, MimeHeaders};
+
+use crate::wrap_line;
+
+/// Returns whether `part` is format=flowed text (RFC 3676), and whether its soft line breaks
+/// come with a space to delete.
+pub(crate) fn get_flowed(part: &MessagePart<'_>) -> Option<bool> {
+    let ct = part.content_type()?;
+    if !ct.attribute("format")?.eq_ignore_ascii_case("flowed") {
+        return None;
+    }
+
+    Some(
+        ct.attribute("delsp")
+            .is_some_and(|d| d.eq_ignore_ascii_case("yes")),
+    )
+}
+
+/// Joins the lines of a format=flowed `text` that end with a soft line break, so that the
+/// paragraphs can then be wrapped again at our own width.
+pub(crate) fn unflow(text: &str, delsp: bool) -> String {
+    let mut unflowed = String::new();
+    let mut flowing: Option<usize> = None;
+
+    for line in text.lines() {
+        let depth = line.chars().take_while(|c| *c == '>').count();
+        let content = &line[depth..];
+        // Senders stuff a space in front of the lines that could be mistaken for quotes.
+        let content = content.strip_prefix(' ').unwrap_or(content);
+
+        // A paragraph never flows into a different quote depth.
+        if flowing != Some(depth) {
+            if flowing.is_some() {
+                unflowed.push('\n');
+            }
+
+            unflowed.push_str(&">".repeat(depth));
+            if depth > 0 && !content.is_empty() {
+                unflowed.push(' ');
+            }
+        }
+
+        // The signature separator is the one line ending with a space that doesn't flow.
+        if content.ends_with(' ') && content != "-- " {
+            unflowed.push_str(if delsp {
+                &content[..content.len() - 1]
+            } else {
+                content
+            });
+            flowing = Some(depth);
+        } else {
+            unflowed.push_str(content);
+            unflowed.push('\n');
+            flowing = None;
+        }
+    }
+
+    if flowing.is_some() {
+        unflowed.push('\n');
+    }
+
+    unflowed
+}
+
+/// Splits `line` into how many times it's already quoted, and the quoted text.
+fn split_quote(line: &str) -> (usize, &str) {
+    let mut depth = 0;
+    let mut rest = line;
+
+    while let Some(tail) = rest.strip_prefix('>') {
+        depth += 1;
+        // "> > text" is the same quote as ">> text".
+        rest = match tail.strip_prefix(' ') {
+            Some(t) if t.starts_with('>') => t,
+            _ => tail,
+        };
+    }
+
+    if depth == 0 {
+        return (0, line);
+    }
+
+    (depth, rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+/// Quotes `line` one level deeper than it already is, with its text wrapped at `width`
+/// characters. The lines that don't need wrapping are kept as is, trailing spaces included,
+/// since they might matter to a diff.
+pub(crate) fn quote_line(line: &str, width: usize) -> Vec<String> {
+    let (depth, text) = split_quote(line);
+    let prefix = ">".repeat(depth + 1);
+
+    if depth > 0 && text.is_empty() {
+        return vec![prefix];
+    }
+
+    wrap_line(text, width)
+        .into_iter()
+        .map(|l| format!("{prefix} {l}"))
+        .collect()
+}
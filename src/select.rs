@@ -0,0 +1,102 @@
+//! `--select`: a screen listing the patches of the series, to choose the tags each of them gets
+//! before all the replies are sent in one go.
+
+use std::{borrow::Cow, fmt::Write as _};
+
+use mail_parser::Message;
+
+use crate::{parse_patch_tags, prompt, unfold_header_value, Args, Error};
+
+/// The tags that can be chosen, and the letter they show as.
+const TAGS: [(&str, char); 4] = [
+    ("Acked-by", 'A'),
+    ("Reviewed-by", 'R'),
+    ("Tested-by", 'T'),
+    ("Signed-off-by", 'S'),
+];
+
+fn format_list(messages: &[Message<'_>], chosen: &[[bool; 4]]) -> String {
+    let mut list = String::from("Tags to give to each patch:\n\n");
+
+    for (index, (msg, tags)) in messages.iter().zip(chosen).enumerate() {
+        let boxes: String = TAGS
+            .iter()
+            .zip(tags)
+            .map(|((_, letter), &on)| if on { *letter } else { '-' })
+            .collect();
+        let subject = unfold_header_value(msg.subject().unwrap_or_default());
+
+        let _ = writeln!(list, "{:3}. [{boxes}] {subject}", index + 1);
+    }
+
+    list.push_str(
+        "\nA: acked, R: reviewed, T: tested, S: signed-off. The patches without any tag are \
+         skipped.\n",
+    );
+    list
+}
+
+/// Flips the tags of `answer`, in the PATCHES:TAGS form of --for, such as 1-3:reviewed.
+fn toggle(answer: &str, chosen: &mut [[bool; 4]]) -> Result<(), String> {
+    let tags = parse_patch_tags(answer)?;
+    if tags.last() > chosen.len() {
+        return Err(format!("there are only {} patches", chosen.len()));
+    }
+
+    for &(first, last) in &tags.patches {
+        for patch in &mut chosen[first - 1..last] {
+            for key in &tags.keys {
+                if let Some(i) = TAGS.iter().position(|(k, _)| k == key) {
+                    patch[i] = !patch[i];
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the options of the reply to each patch the user chose tags for, starting from the
+/// tags given on the command line.
+pub(crate) fn choose<'a, 'm>(
+    args: &'a Args,
+    messages: &'a [Message<'m>],
+) -> Result<Vec<(&'a Message<'m>, Cow<'a, Args>)>, Error> {
+    let given = [args.acked, args.reviewed, args.tested, args.signed_off];
+    let mut chosen = vec![given; messages.len()];
+
+    loop {
+        println!("{}", format_list(messages, &chosen));
+
+        let answer = prompt::prompt(
+            "Toggle tags, such as 1-3:reviewed or 2:acked,tested, [s]end or [q]uit",
+            Some("s"),
+        )?;
+        match answer.to_lowercase().as_str() {
+            "s" | "send" => break,
+            "q" | "quit" => return Err(Error::Aborted),
+            _ => {
+                if let Err(e) = toggle(&answer, &mut chosen) {
+                    println!("{e}");
+                }
+            }
+        }
+    }
+
+    Ok(messages
+        .iter()
+        .zip(chosen)
+        .filter(|(_, tags)| tags.contains(&true))
+        .map(|(msg, [acked, reviewed, tested, signed_off])| {
+            let args = Args {
+                acked,
+                reviewed,
+                tested,
+                signed_off,
+                ..args.clone()
+            };
+
+            (msg, Cow::Owned(args))
+        })
+        .collect())
+}
@@ -0,0 +1,84 @@
+//! Handling of whole patch series, as found in an mbox or a Maildir.
+
+use mail_parser::Message;
+
+/// Returns the position of a patch in its series and the series length, from the `[PATCH 3/15]`
+/// like prefix of its subject.
+fn get_patch_number(subject: &str) -> Option<(usize, usize)> {
+    let (_, prefix) = subject.split_once('[')?;
+    let (prefix, _) = prefix.split_once(']')?;
+
+    prefix.split_whitespace().find_map(|token| {
+        let (index, total) = token.split_once('/')?;
+
+        Some((index.parse().ok()?, total.parse().ok()?))
+    })
+}
+
+pub(crate) fn is_cover_letter(msg: &Message<'_>) -> bool {
+    msg.subject()
+        .and_then(get_patch_number)
+        .is_some_and(|(index, _)| index == 0)
+}
+
+/// Drops the replies from `messages`, to only keep the patches and their cover letter.
+pub(crate) fn retain_patches(messages: &mut Vec<Message<'_>>) {
+    messages.retain(|m| {
+        !m.subject().is_some_and(|s| {
+            s.trim_start()
+                .get(..3)
+                .is_some_and(|p| p.eq_ignore_ascii_case("re:"))
+        })
+    });
+}
+
+/// Sorts the messages in the order of the series, the cover letter first. Messages that aren't
+/// numbered keep their relative order, after the others.
+pub(crate) fn sort(messages: &mut [Message<'_>]) {
+    messages.sort_by_key(|m| {
+        m.subject()
+            .and_then(get_patch_number)
+            .map_or(usize::MAX, |(index, _)| index)
+    });
+}
+
+/// Returns the number of patches of the series, from their `[PATCH 3/15]` like prefix, or else
+/// from the number of messages besides the cover letter.
+pub(crate) fn len(messages: &[Message<'_>]) -> usize {
+    messages
+        .iter()
+        .filter_map(|m| m.subject().and_then(get_patch_number))
+        .map(|(_, total)| total)
+        .max()
+        .unwrap_or_else(|| messages.len().saturating_sub(1))
+}
+
+/// Returns the index of the cover letter of the series, if there's one.
+///
+/// Series sent without a numbered subject are recognized from their thread: the cover letter is
+/// the message without a diff that all the others reply to.
+pub(crate) fn find_cover_letter(messages: &[Message<'_>]) -> Option<usize> {
+    if let Some(index) = messages.iter().position(is_cover_letter) {
+        return Some(index);
+    }
+
+    if messages.len() < 2 {
+        return None;
+    }
+
+    messages.iter().position(|candidate| {
+        let Some(id) = candidate.message_id() else {
+            return false;
+        };
+
+        let has_diff = candidate
+            .body_text(0)
+            .is_some_and(|b| b.lines().any(|l| l.starts_with("diff --git ")));
+
+        !has_diff
+            && messages
+                .iter()
+                .filter(|m| m.message_id() != Some(id))
+                .all(|m| m.in_reply_to().as_text() == Some(id))
+    })
+}
@@ -0,0 +1,121 @@
+//! Review sessions of a series, so that `--resume` picks up where an interrupted one stopped
+//! rather than sending the same replies again.
+//!
+//! A session lives in `$XDG_STATE_HOME/acker/sessions/`, in a file named after the Message-ID
+//! of the cover letter, or of the first patch of a series without one. It has a line per patch
+//! we replied to, with its Message-ID and the trailers we gave it, separated by tabs.
+
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::PathBuf,
+};
+
+use mail_parser::Message;
+
+use crate::{series, state, Error, TrailerSet};
+
+const SESSIONS_DIR: &str = "sessions";
+
+/// Returns the Message-ID the series of `messages` is known by: the one of its cover letter, or
+/// else the one at the root of the thread.
+fn get_series_id(messages: &[Message<'_>]) -> Option<String> {
+    series::find_cover_letter(messages)
+        .and_then(|i| messages[i].message_id())
+        .or_else(|| {
+            messages
+                .iter()
+                .find_map(|m| m.references().as_text_list()?.into_iter().next())
+        })
+        .or_else(|| messages.first().and_then(Message::message_id))
+        .map(String::from)
+}
+
+/// Returns `id` as a file name, without the characters that don't belong in one.
+fn file_name(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "@.-_+=".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The patches of a series we replied to so far.
+#[derive(Debug, Default)]
+pub(crate) struct Session {
+    path: Option<PathBuf>,
+    replied: HashMap<String, Vec<String>>,
+}
+
+impl Session {
+    /// Loads the session of the series of `messages`, an empty one if it's new. A single
+    /// message doesn't need one.
+    pub(crate) fn load(messages: &[Message<'_>]) -> Result<Self, Error> {
+        if messages.len() < 2 {
+            return Ok(Session::default());
+        }
+
+        let Some(path) = state::state_dir()
+            .zip(get_series_id(messages))
+            .map(|(dir, id)| dir.join(SESSIONS_DIR).join(file_name(&id)))
+        else {
+            return Ok(Session::default());
+        };
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::Io(path, e)),
+        };
+
+        let replied = content
+            .lines()
+            .filter_map(|l| {
+                let mut fields = l.split('\t');
+                let id = fields.next().filter(|id| !id.is_empty())?;
+                Some((id.to_string(), fields.map(String::from).collect()))
+            })
+            .collect();
+
+        Ok(Session {
+            path: Some(path),
+            replied,
+        })
+    }
+
+    /// Returns the trailers we gave to `message_id` in the session, if we replied to it.
+    pub(crate) fn replied(&self, message_id: &str) -> Option<&[String]> {
+        self.replied.get(message_id).map(Vec::as_slice)
+    }
+
+    /// Records that we replied to `message_id` with `trailers`.
+    pub(crate) fn record(&mut self, message_id: &str, trailers: &TrailerSet) -> Result<(), Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let trailers: Vec<_> = trailers.iter().map(ToString::to_string).collect();
+        let mut line = String::from(message_id);
+        for trailer in &trailers {
+            line.push('\t');
+            line.push_str(trailer);
+        }
+        self.replied.insert(message_id.to_string(), trailers);
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| Error::Io(dir.to_path_buf(), e))?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| writeln!(f, "{line}"))
+            .map_err(|e| Error::Io(path.clone(), e))
+    }
+}
@@ -0,0 +1,125 @@
+//! Signature of the reply: inline PGP through gpg, just like git signs its commits and tags, or
+//! S/MIME through openssl for the places whose policy asks for it.
+//!
+//! S/MIME takes the certificate of `acker.smimeCert` and the private key of `acker.smimeKey`,
+//! both PEM files, the key defaulting to the certificate file.
+
+use std::{
+    io::Write as _,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use gix_config::{path::interpolate::Context as PathContext, File as GitFile};
+use lettre::message::{
+    header::{ContentDisposition, ContentTransferEncoding, ContentType},
+    MultiPart, SinglePart,
+};
+
+use crate::{get_config_string, Error};
+
+const OPENSSL: &str = "openssl";
+
+/// Runs `cmd`, the command line of `program`, with `input` on its standard input, and returns
+/// what it printed.
+fn filter(program: &str, mut cmd: Command, input: Vec<u8>) -> Result<Vec<u8>, Error> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Io(PathBuf::from(&program), e))?;
+
+    // Feed gpg from another thread, so that neither of us waits on a full pipe.
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| Error::Sign(program.to_string(), String::from("no stdin")))?;
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| Error::Io(PathBuf::from(program), e))?;
+    writer
+        .join()
+        .map_err(|_| {
+            Error::Sign(
+                program.to_string(),
+                String::from("couldn't write the reply"),
+            )
+        })?
+        .map_err(|e| Error::Io(PathBuf::from(program), e))?;
+
+    if !output.status.success() {
+        return Err(Error::Sign(program.to_string(), output.status.to_string()));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Returns `body` clear-signed with `user.signingkey`, or the default key of gpg.
+pub(crate) fn clearsign(cfg: &GitFile<'_>, body: &str) -> Result<String, Error> {
+    let program = get_config_string(cfg, "gpg.program").unwrap_or_else(|| String::from("gpg"));
+
+    let mut cmd = Command::new(&program);
+    cmd.arg("--clearsign");
+    if let Some(key) = get_config_string(cfg, "user.signingkey") {
+        cmd.arg("--local-user").arg(key);
+    }
+
+    String::from_utf8(filter(&program, cmd, body.as_bytes().to_vec())?)
+        .map_err(|_| Error::Sign(program, String::from("the signed text isn't UTF-8")))
+}
+
+fn get_path(cfg: &GitFile<'_>, key: &'static str) -> Result<Option<PathBuf>, Error> {
+    cfg.path_by_key(key)
+        .map(|p| {
+            p.interpolate(PathContext {
+                home_dir: home::home_dir().as_deref(),
+                ..Default::default()
+            })
+            .map(std::borrow::Cow::into_owned)
+            .map_err(|e| Error::InvalidConfig(key, e.to_string()))
+        })
+        .transpose()
+}
+
+/// Returns `body` as a `multipart/signed` S/MIME body, signed with the certificate and key of
+/// the configuration.
+pub(crate) fn smime(cfg: &GitFile<'_>, body: &str) -> Result<MultiPart, Error> {
+    let cert = get_path(cfg, "acker.smimeCert")?.ok_or(Error::MissingConfig("acker.smimeCert"))?;
+    let key = get_path(cfg, "acker.smimeKey")?.unwrap_or_else(|| cert.clone());
+
+    let part = SinglePart::builder()
+        .header(ContentType::TEXT_PLAIN)
+        .body(body.to_string());
+
+    // The signature covers the part as it's sent, up to the line break before the boundary.
+    let mut signed = part.formatted();
+    signed.truncate(signed.len().saturating_sub(2));
+
+    let mut cmd = Command::new(OPENSSL);
+    cmd.args([
+        "cms", "-sign", "-binary", "-md", "sha256", "-outform", "DER",
+    ])
+    .arg("-signer")
+    .arg(&cert)
+    .arg("-inkey")
+    .arg(&key);
+    let signature = filter(OPENSSL, cmd, signed)?;
+
+    Ok(MultiPart::signed(
+        String::from("application/pkcs7-signature"),
+        String::from("sha-256"),
+    )
+    .singlepart(part)
+    .singlepart(
+        SinglePart::builder()
+            .header(
+                ContentType::parse("application/pkcs7-signature; name=\"smime.p7s\"")
+                    .map_err(|e| Error::Sign(String::from(OPENSSL), e.to_string()))?,
+            )
+            .header(ContentDisposition::attachment("smime.p7s"))
+            .header(ContentTransferEncoding::Base64)
+            .body(signature),
+    ))
+}
@@ -0,0 +1,181 @@
+//! Record of the trailers already sent, so that a resend or a second run doesn't get acked twice.
+//!
+//! The record lives in `$XDG_STATE_HOME/acker/sent.db`, one `Message-ID`, trailer key and address
+//! per line, separated by tabs, followed by when we sent it and the list or domain it went to for
+//! `acker stats`, and the title of the patch, to recognize the new versions of the ones we nacked.
+
+use std::{
+    collections::HashSet,
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use crate::Error;
+
+const STATE_FILE: &str = "sent.db";
+
+/// The trailer key of `acker --nack`, in lowercase.
+const NACK_KEY: &str = "nacked-by";
+
+pub(crate) fn state_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| home::home_dir().map(|h| h.join(".local").join("state")))?;
+
+    Some(base.join("acker"))
+}
+
+fn entry(message_id: &str, key: &str, address: &str) -> (String, String, String) {
+    (
+        message_id.to_string(),
+        key.to_ascii_lowercase(),
+        address.to_ascii_lowercase(),
+    )
+}
+
+/// A trailer we sent, as `acker stats` counts them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Sent {
+    /// The trailer key, in lowercase.
+    pub(crate) key: String,
+    /// When we sent it, in seconds since the epoch.
+    pub(crate) time: u64,
+    /// The mailing list the patch came through, or else the domain of its author.
+    pub(crate) list: String,
+}
+
+/// The trailers sent so far.
+#[derive(Debug, Default)]
+pub(crate) struct SentLog {
+    path: Option<PathBuf>,
+    entries: HashSet<(String, String, String)>,
+    history: Vec<Sent>,
+    nacked: HashSet<String>,
+}
+
+impl SentLog {
+    /// Loads the record, an empty one if we never sent anything.
+    pub(crate) fn load() -> Result<Self, Error> {
+        let Some(path) = state_dir().map(|d| d.join(STATE_FILE)) else {
+            return Ok(SentLog::default());
+        };
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::Io(path, e)),
+        };
+
+        let mut entries = HashSet::new();
+        let mut history = Vec::new();
+        let mut nacked = HashSet::new();
+        for line in content.lines() {
+            let fields: Vec<_> = line.split('\t').collect();
+            let [id, key, address, rest @ ..] = fields.as_slice() else {
+                continue;
+            };
+
+            let entry = entry(id, key, address);
+            // The records written before acker stats don't tell when they were sent.
+            if let [time, list, title @ ..] = rest {
+                if let Ok(time) = time.parse() {
+                    history.push(Sent {
+                        key: entry.1.clone(),
+                        time,
+                        list: list.to_string(),
+                    });
+                }
+
+                match title.first() {
+                    Some(title) if entry.1 == NACK_KEY => {
+                        nacked.insert(title.to_string());
+                    }
+                    _ => {}
+                }
+            }
+            entries.insert(entry);
+        }
+
+        Ok(SentLog {
+            path: Some(path),
+            entries,
+            history,
+            nacked,
+        })
+    }
+
+    /// Returns the trailers we sent, in the order we sent them.
+    pub(crate) fn history(&self) -> &[Sent] {
+        &self.history
+    }
+
+    /// Returns whether we already sent the trailer `key` for `address` in reply to `message_id`.
+    pub(crate) fn contains(&self, message_id: &str, key: &str, address: &str) -> bool {
+        self.entries.contains(&entry(message_id, key, address))
+    }
+
+    /// Returns whether we nacked a patch titled `title`, in any of its versions.
+    pub(crate) fn nacked(&self, title: &str) -> bool {
+        self.nacked.contains(title)
+    }
+
+    /// Returns the trailer keys and addresses we sent in reply to `message_id`, sorted.
+    pub(crate) fn given(&self, message_id: &str) -> Vec<(&str, &str)> {
+        let mut given: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(id, _, _)| id == message_id)
+            .map(|(_, key, address)| (key.as_str(), address.as_str()))
+            .collect();
+        given.sort_unstable();
+
+        given
+    }
+
+    /// Records that we sent the trailer `key` for `address` in reply to `message_id`, the patch
+    /// `title` that went to `list`.
+    pub(crate) fn record(
+        &mut self,
+        message_id: &str,
+        key: &str,
+        address: &str,
+        list: &str,
+        title: &str,
+    ) -> Result<(), Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if !self.entries.insert(entry(message_id, key, address)) {
+            return Ok(());
+        }
+
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.history.push(Sent {
+            key: key.to_ascii_lowercase(),
+            time,
+            list: list.to_string(),
+        });
+        if key.eq_ignore_ascii_case(NACK_KEY) {
+            self.nacked.insert(title.to_string());
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| Error::Io(dir.to_path_buf(), e))?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| {
+                writeln!(f, "{message_id}\t{key}\t{address}\t{time}\t{list}\t{title}")
+            })
+            .map_err(|e| Error::Io(path.clone(), e))
+    }
+}
@@ -0,0 +1,119 @@
+//! `acker stats`: how many tags we gave, by week and by list, for the "what did I review this
+//! cycle" reports.
+
+use std::{collections::BTreeMap, fmt::Write as _, process::ExitCode, time::SystemTime};
+
+use mail_parser::DateTime;
+
+use crate::{
+    state::{Sent, SentLog},
+    Error,
+};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Parses a `--since` age such as `2weeks` or `3months`, into seconds.
+pub(crate) fn parse_since(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (count, unit) = s.split_at(split);
+
+    let count: u64 = count
+        .parse()
+        .map_err(|_| format!("{s} doesn't start with a number"))?;
+    let days = match unit.trim().trim_start_matches('.') {
+        "d" | "day" | "days" => 1,
+        "w" | "week" | "weeks" => 7,
+        "m" | "month" | "months" => 30,
+        "y" | "year" | "years" => 365,
+        _ => {
+            return Err(format!(
+                "{s} isn't a number of days, weeks, months or years"
+            ))
+        }
+    };
+
+    Ok(count.saturating_mul(days * DAY))
+}
+
+/// Writes the trailer key, stored in lowercase, the way it's usually spelled, as in
+/// `Reviewed-by`.
+fn spell_key(key: &str) -> String {
+    let mut chars = key.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Returns the Monday starting the week of `time`, as a date.
+fn week_of(time: u64) -> String {
+    let days = time / DAY;
+    // The epoch was a Thursday.
+    let monday = days - (days + 3) % 7;
+    let date = DateTime::from_timestamp(i64::try_from(monday * DAY).unwrap_or_default());
+
+    format!("{:04}-{:02}-{:02}", date.year, date.month, date.day)
+}
+
+fn count_keys<'a>(sent: impl Iterator<Item = &'a Sent>) -> String {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for s in sent {
+        *counts.entry(&s.key).or_default() += 1;
+    }
+
+    counts
+        .iter()
+        .map(|(key, count)| format!("{}: {count}", spell_key(key)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns the report of the trailers of `history` sent after `since`, in seconds since the epoch.
+pub(crate) fn report(history: &[Sent], since: u64) -> String {
+    let sent: Vec<_> = history.iter().filter(|s| s.time >= since).collect();
+    if sent.is_empty() {
+        return String::from("No tags given.\n");
+    }
+
+    let mut report = format!("Total: {}\n", count_keys(sent.iter().copied()));
+
+    let mut weeks: BTreeMap<String, Vec<&Sent>> = BTreeMap::new();
+    let mut lists: BTreeMap<&str, Vec<&Sent>> = BTreeMap::new();
+    for s in &sent {
+        weeks.entry(week_of(s.time)).or_default().push(s);
+        lists.entry(&s.list).or_default().push(s);
+    }
+
+    report.push_str("\nBy week:\n");
+    for (week, sent) in &weeks {
+        let _ = writeln!(report, "  {week}: {}", count_keys(sent.iter().copied()));
+    }
+
+    // The busiest lists first.
+    let mut lists: Vec<_> = lists.into_iter().collect();
+    lists.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then(a.0.cmp(b.0)));
+
+    report.push_str("\nBy list or domain:\n");
+    for (list, sent) in &lists {
+        let list = if list.is_empty() { "unknown" } else { list };
+        let _ = writeln!(report, "  {list}: {}", count_keys(sent.iter().copied()));
+    }
+
+    report
+}
+
+/// `acker stats`: prints the report of the tags sent in the last `since` seconds, or ever.
+pub(crate) fn run(since: Option<u64>) -> Result<ExitCode, Error> {
+    let log = SentLog::load()?;
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    print!(
+        "{}",
+        report(log.history(), since.map_or(0, |s| now.saturating_sub(s)))
+    );
+    Ok(ExitCode::SUCCESS)
+}
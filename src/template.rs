@@ -0,0 +1,106 @@
+//! Layout of the reply, from the file at `acker.template` or `~/.config/acker/template`.
+//!
+//! A template is plain text with `{placeholder}` in it, `{{` and `}}` being literal braces. A
+//! line only made of a placeholder with nothing to put in it, like `{greeting}` without a
+//! `bodyPrefix`, is left out.
+
+use std::path::PathBuf;
+
+use gix_config::{path::interpolate::Context as PathContext, File as GitFile};
+
+use crate::Error;
+
+/// The layout acker always had.
+const DEFAULT_TEMPLATE: &str = "{greeting}
+{attribution}
+{quoted_body}
+
+{trailers}
+
+{signoff}
+{first_name}
+";
+
+fn default_template_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| home::home_dir().map(|h| h.join(".config")))
+        .map(|d| d.join("acker").join("template"))
+}
+
+/// Returns the template to lay out the reply with.
+pub(crate) fn get_template(cfg: &GitFile<'_>) -> Result<String, Error> {
+    let path = match cfg.path_by_key("acker.template") {
+        Some(p) => p
+            .interpolate(PathContext {
+                home_dir: home::home_dir().as_deref(),
+                ..Default::default()
+            })
+            .map_err(|e| Error::InvalidConfig("acker.template", e.to_string()))?
+            .into_owned(),
+        None => match default_template_path().filter(|p| p.exists()) {
+            Some(p) => p,
+            None => return Ok(String::from(DEFAULT_TEMPLATE)),
+        },
+    };
+
+    std::fs::read_to_string(&path).map_err(|e| Error::Io(path, e))
+}
+
+/// Returns the placeholder a line is made of, if it's only that.
+fn lone_placeholder(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix('{')?
+        .strip_suffix('}')
+        .filter(|p| !p.contains(['{', '}']))
+}
+
+/// Fills `template` with `values`, a list of placeholders and what to replace them with.
+pub(crate) fn render(template: &str, values: &[(&str, &str)]) -> Result<String, Error> {
+    let value = |name: &str| {
+        values
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.strip_suffix('\n').unwrap_or(v))
+            .ok_or_else(|| {
+                Error::InvalidConfig("acker.template", format!("unknown placeholder {{{name}}}"))
+            })
+    };
+
+    let mut rendered = String::new();
+    for line in template.lines() {
+        if let Some(name) = lone_placeholder(line) {
+            if value(name)?.is_empty() {
+                continue;
+            }
+        }
+
+        let mut rest = line;
+        while let Some(start) = rest.find(['{', '}']) {
+            rendered.push_str(&rest[..start]);
+            let tail = &rest[start..];
+
+            if tail.starts_with("{{") || tail.starts_with("}}") {
+                rendered.push_str(&tail[..1]);
+                rest = &tail[2..];
+                continue;
+            }
+
+            let Some(end) = tail.find('}').filter(|_| tail.starts_with('{')) else {
+                return Err(Error::InvalidConfig(
+                    "acker.template",
+                    format!("unbalanced brace in {line:?}"),
+                ));
+            };
+
+            rendered.push_str(value(&tail[1..end])?);
+            rest = &tail[end + 1..];
+        }
+
+        rendered.push_str(rest);
+        rendered.push('\n');
+    }
+
+    Ok(rendered)
+}
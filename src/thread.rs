@@ -0,0 +1,223 @@
+//! `--show-thread`: the replies the message already got, from a local mbox or Maildir or from
+//! the archive, to see whether someone gave the same tag or objected before we send ours.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use gix_config::File as GitFile;
+use lettre::message::Mailbox;
+use mail_parser::{Message, MessageParser};
+
+use crate::{
+    fetch, get_mail_body, get_trailer_address, is_trailer_line, read_messages, split_mbox,
+    unfold_header_value, Args, Error, TrailerSet,
+};
+
+/// The messages of the thread, as they were read.
+pub(crate) struct Thread {
+    source: PathBuf,
+    buffers: Vec<Vec<u8>>,
+}
+
+/// Returns whether `reply` is somewhere below `id` in the thread.
+fn is_reply_to(reply: &Message<'_>, id: &str) -> bool {
+    reply.message_id() != Some(id)
+        && [reply.references(), reply.in_reply_to()]
+            .iter()
+            .filter_map(|h| h.as_text_list())
+            .flatten()
+            .any(|r| r == id)
+}
+
+/// Removes the blank lines at both ends of `lines`, and the ones following another.
+fn squeeze_blank_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut squeezed: Vec<&str> = Vec::new();
+    for line in lines {
+        if line.is_empty() && squeezed.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+
+        squeezed.push(line);
+    }
+
+    while squeezed.last().is_some_and(|l| l.is_empty()) {
+        squeezed.pop();
+    }
+
+    squeezed
+}
+
+/// Returns what the author of `reply` wrote: its text without the quotes, the attribution lines
+/// and the signature.
+fn get_own_text(body: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in body.lines().take_while(|l| *l != "-- ") {
+        let line = line.trim_end();
+        if line.starts_with('>') {
+            // The line introducing the quote is of no use without it.
+            if lines.last().is_some_and(|l| l.ends_with("wrote:")) {
+                lines.pop();
+            }
+            continue;
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Returns whether `line` is a tag someone gives, such as a Reviewed-by.
+fn is_tag(line: &str) -> bool {
+    line.contains("-by:") && is_trailer_line(line)
+}
+
+/// Returns the sender of `msg`, as it shows in its From header.
+fn get_sender(msg: &Message<'_>) -> String {
+    msg.from()
+        .and_then(|f| f.first())
+        .map(|a| match (a.name(), a.address()) {
+            (Some(name), Some(address)) => format!("{name} <{address}>"),
+            (name, address) => name.or(address).unwrap_or_default().to_string(),
+        })
+        .unwrap_or_default()
+}
+
+impl Thread {
+    /// Returns the replies to the message `id`, oldest first.
+    fn get_replies(&self, id: &str) -> Result<Vec<Message<'_>>, Error> {
+        let mut replies = Vec::new();
+        for buffer in &self.buffers {
+            let reply = MessageParser::default()
+                .parse(buffer)
+                .ok_or_else(|| Error::InvalidMessage(self.source.clone()))?;
+
+            if is_reply_to(&reply, id) {
+                replies.push(reply);
+            }
+        }
+
+        replies.sort_by_key(|r| r.date().map(mail_parser::DateTime::to_timestamp));
+        Ok(replies)
+    }
+
+    /// Returns what the replies to `msg` already have of ours: the replies `user` sent, and the
+    /// ones giving one of `trailers`.
+    pub(crate) fn find_duplicates(
+        &self,
+        args: &Args,
+        msg: &Message<'_>,
+        user: &Mailbox,
+        trailers: &TrailerSet,
+    ) -> Result<Vec<String>, Error> {
+        let Some(id) = msg.message_id() else {
+            return Ok(Vec::new());
+        };
+
+        let mut duplicates = Vec::new();
+        for reply in self.get_replies(id)? {
+            let sender = get_sender(&reply);
+            let ours = reply
+                .from()
+                .and_then(|f| f.first())
+                .and_then(mail_parser::Addr::address)
+                .is_some_and(|a| a.eq_ignore_ascii_case(user.email.as_ref()));
+            if ours {
+                let date = reply.date().map(mail_parser::DateTime::to_rfc822);
+                duplicates.push(format!(
+                    "we already replied to it on {}",
+                    date.unwrap_or_default()
+                ));
+            }
+
+            let body = get_mail_body(args, &reply)?;
+            for tag in get_own_text(&body)
+                .into_iter()
+                .filter(|l| !ours && is_tag(l))
+            {
+                let Some((key, Some(address))) = tag
+                    .split_once(':')
+                    .map(|(k, v)| (k.trim(), get_trailer_address(v)))
+                else {
+                    continue;
+                };
+
+                let given = trailers.iter().find(|t| {
+                    t.key.eq_ignore_ascii_case(key)
+                        && get_trailer_address(&t.value)
+                            .is_some_and(|a| a.eq_ignore_ascii_case(&address))
+                });
+                if let Some(trailer) = given {
+                    duplicates.push(format!("{sender} already gave {trailer}"));
+                }
+            }
+        }
+
+        Ok(duplicates)
+    }
+
+    /// Reads the thread of `msg` from `source`, or downloads it from the archive if `source` is
+    /// empty.
+    pub(crate) fn load(cfg: &GitFile<'_>, source: &Path, msg: &Message<'_>) -> Result<Self, Error> {
+        if !source.as_os_str().is_empty() {
+            return Ok(Thread {
+                source: source.to_path_buf(),
+                buffers: read_messages(source)?,
+            });
+        }
+
+        let id = msg.message_id().ok_or(Error::MissingHeader("Message-ID"))?;
+        let url = fetch::get_url(cfg, id, true);
+        let source = PathBuf::from(&url);
+
+        Ok(Thread {
+            buffers: split_mbox(&source, fetch::fetch(&url)?)?,
+            source,
+        })
+    }
+
+    /// Returns the replies `msg` got so far, oldest first, with the tags they give and what their
+    /// authors wrote.
+    pub(crate) fn format_replies(&self, args: &Args, msg: &Message<'_>) -> Result<String, Error> {
+        let subject = unfold_header_value(msg.subject().unwrap_or_default());
+        let Some(id) = msg.message_id() else {
+            return Ok(String::new());
+        };
+
+        let replies = self.get_replies(id)?;
+        if replies.is_empty() {
+            return Ok(format!("No replies to \"{subject}\" so far.\n\n"));
+        }
+
+        let mut text = format!("Replies to \"{subject}\" so far:\n");
+        for reply in &replies {
+            let date = reply.date().map(mail_parser::DateTime::to_rfc822);
+            let _ = writeln!(
+                text,
+                "\n--- {}, {}",
+                get_sender(reply),
+                date.unwrap_or_default()
+            );
+
+            let body = get_mail_body(args, reply)?;
+            let (tags, own): (Vec<_>, Vec<_>) =
+                get_own_text(&body).into_iter().partition(|l| is_tag(l));
+            for tag in tags {
+                let _ = writeln!(text, "Gives: {}", tag.trim());
+            }
+
+            for line in squeeze_blank_lines(own) {
+                if line.is_empty() {
+                    text.push('\n');
+                } else {
+                    let _ = writeln!(text, "  {line}");
+                }
+            }
+        }
+        text.push('\n');
+
+        Ok(text)
+    }
+}
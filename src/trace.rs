@@ -0,0 +1,96 @@
+//! Trace of the SMTP dialogue with the server, for `--trace-smtp`, and of what we do, for
+//! `--verbose`.
+//!
+//! lettre logs every command it sends and every response it gets at the debug level, including
+//! the credentials exchanged during authentication, so we mask those before printing anything.
+//! Several messages can be on their way at once, each over its own connection, so every send
+//! gets a span of its own, and we follow the AUTH exchanges span by span.
+
+use std::{
+    io::{self, Write},
+    sync::{Mutex, PoisonError},
+};
+
+use tracing_subscriber::{
+    filter::{LevelFilter, Targets},
+    fmt,
+    prelude::*,
+};
+
+/// The target of the span every message is sent in.
+pub(crate) const SMTP_TARGET: &str = "acker::smtp";
+
+const MASK: &str = "<masked>";
+
+/// The spans, as they're printed in front of lettre's lines, in the middle of an AUTH exchange,
+/// in which case everything they send is a credential.
+static IN_AUTH: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub(crate) fn mask_credentials(line: &str) -> String {
+    let mut in_auth = IN_AUTH.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if let Some((prefix, command)) = line.split_once("Wrote: ") {
+        if let Some(args) = command.strip_prefix("AUTH ") {
+            in_auth.push(prefix.to_string());
+
+            // Keep the mechanism, but hide the initial response if there's one.
+            let mechanism = args.split([' ', '<']).next().unwrap_or_default();
+            return format!("{prefix}Wrote: AUTH {mechanism} {MASK}\n");
+        }
+
+        if in_auth.iter().any(|p| p == prefix) {
+            return format!("{prefix}Wrote: {MASK}\n");
+        }
+    } else if let Some((prefix, response)) = line.split_once("<< ") {
+        // Anything but a challenge ends the exchange.
+        if !response.starts_with("334") {
+            in_auth.retain(|p| p != prefix);
+        }
+    }
+
+    line.to_string()
+}
+
+struct TraceWriter;
+
+impl Write for TraceWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let line = String::from_utf8_lossy(buf);
+
+        io::stderr().write_all(mask_credentials(&line).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Prints our diagnostics on stderr, more of them with each `verbose` level, and lettre's SMTP
+/// dialogue with `trace_smtp` or from `-vv` on.
+pub(crate) fn enable(trace_smtp: bool, verbose: u8) {
+    let level = match verbose {
+        0 => LevelFilter::OFF,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+
+    let mut targets = Targets::new().with_target("acker", level);
+    if trace_smtp || verbose >= 2 {
+        targets = targets
+            .with_target("lettre", LevelFilter::DEBUG)
+            .with_target(SMTP_TARGET, LevelFilter::DEBUG);
+    }
+
+    tracing_subscriber::registry()
+        .with(
+            fmt::layer()
+                .with_writer(|| TraceWriter)
+                .with_ansi(false)
+                .with_target(false)
+                .without_time(),
+        )
+        .with(targets)
+        .init();
+}
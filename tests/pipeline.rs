@@ -0,0 +1,225 @@
+//! The whole of acker, from the messages in `tests/samples` to the replies it sends, caught by a
+//! [`MailTransport::Capture`] rather than going out.
+
+use std::{path::PathBuf, process::ExitCode, str::FromStr, sync::OnceLock};
+
+use acker::{run_with, Capture, Cli, Error, MailTransport};
+use gix_config::File as GitFile;
+
+/// Points the home and XDG directories to an empty one, so that neither the configuration nor
+/// the state of whoever runs the tests gets in the way, nor gets the trailers we send.
+fn isolate() {
+    static HOME: OnceLock<tempfile::TempDir> = OnceLock::new();
+
+    HOME.get_or_init(|| {
+        let home = tempfile::tempdir().unwrap();
+        for var in ["HOME", "XDG_CONFIG_HOME", "XDG_DATA_HOME", "XDG_STATE_HOME"] {
+            std::env::set_var(var, home.path());
+        }
+
+        home
+    });
+}
+
+fn sample(name: &str) -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("samples")
+        .join(name)
+        .display()
+        .to_string()
+}
+
+/// Returns `email` as text, with the headers that change on every run blanked out. The rest is
+/// compared as is, so the quoted empty lines of the expected replies keep the space after `>`.
+fn normalize(email: &[u8]) -> String {
+    let email = String::from_utf8(email.to_vec())
+        .unwrap()
+        .replace("\r\n", "\n");
+
+    email
+        .lines()
+        .map(|l| match l.split_once(": ") {
+            Some((name @ ("Date" | "Message-ID" | "User-Agent"), _)) => format!("{name}: ...\n"),
+            _ => format!("{l}\n"),
+        })
+        .collect()
+}
+
+/// Runs acker with `args`, and returns the replies it sent.
+fn send(args: &[&str]) -> Result<Vec<String>, Error> {
+    isolate();
+
+    let cfg = GitFile::from_str("[user]\n\tname = Maxime Ripard\n\temail = mripard@kernel.org\n")
+        .unwrap();
+    let cli = Cli::try_parse_ordered(["acker"].iter().chain(args)).unwrap();
+    let capture = Capture::default();
+
+    let code = run_with(&cli, cfg, Some(MailTransport::Capture(capture.clone())))?;
+    assert_eq!(code, ExitCode::SUCCESS);
+
+    Ok(capture.messages().iter().map(|m| normalize(m)).collect())
+}
+
+#[test]
+fn non_utf8_patch_is_quoted_in_utf8() {
+    assert_eq!(
+        send(&["-r", &sample("latin1.eml")]).unwrap(),
+        [r#"Date: ...
+Message-ID: ...
+User-Agent: ...
+From: "Maxime Ripard" <mripard@kernel.org>
+Subject: Re: [PATCH] drm/panel: foo: use the timings of the
+ =?utf-8?b?csOpdmlzaW9u?= B
+In-Reply-To: <20261012100000.1-rene@example.fr>
+References: <20261012100000.1-rene@example.fr>
+To: =?utf-8?b?UmVuw6kgRnJhbsOnb2lz?= <rene@example.fr>
+Cc: dri-devel@lists.freedesktop.org, "Maxime Ripard" <mripard@kernel.org>
+Content-Type: text/plain; charset=utf-8
+Content-Transfer-Encoding: quoted-printable
+
+On Mon, 12 Oct 2026 10:00:00 +0200, Ren=C3=A9 Fran=C3=A7ois wrote:
+> The timings of the panel were copied from the wrong data sheet, use the
+> ones of the r=C3=A9vision B.
+>=20
+> Signed-off-by: Ren=C3=A9 Fran=C3=A7ois <rene@example.fr>
+
+Reviewed-by: Maxime Ripard <mripard@kernel.org>
+
+Thanks!
+Maxime
+"#]
+    );
+}
+
+#[test]
+fn signed_multipart_patch_quotes_its_text() {
+    assert_eq!(
+        send(&["-a", &sample("multipart.eml")]).unwrap(),
+        [r#"Date: ...
+Message-ID: ...
+User-Agent: ...
+From: "Maxime Ripard" <mripard@kernel.org>
+Subject: Re: [PATCH v2] clk: sunxi: fix the parent of the bus clock
+In-Reply-To: <20261013093000.1-jane@example.com>
+References: <20261013093000.1-jane@example.com>
+To: "Jane Doe" <jane@example.com>
+Cc: linux-kernel@vger.kernel.org, "Maxime Ripard" <mripard@kernel.org>
+Content-Type: text/plain; charset=utf-8
+Content-Transfer-Encoding: 7bit
+
+On Tue, 13 Oct 2026 09:30:00 +0000, Jane Doe wrote:
+> The bus clock of the A64 comes from the AHB1 clock, not from the APB1
+> one as the driver says.
+> 
+> Fixes: 0123456789ab ("clk: sunxi: add the A64 clocks")
+> Signed-off-by: Jane Doe <jane@example.com>
+
+Acked-by: Maxime Ripard <mripard@kernel.org>
+
+Thanks!
+Maxime
+"#]
+    );
+}
+
+#[test]
+fn missing_headers_are_done_without() {
+    assert_eq!(
+        send(&["-t", &sample("minimal.eml")]).unwrap(),
+        [r#"Date: ...
+Message-ID: ...
+User-Agent: ...
+From: "Maxime Ripard" <mripard@kernel.org>
+Subject: Re: [PATCH] foo: drop the unused bar
+In-Reply-To: <20261014.1-bob@example.org>
+References: <20261014.1-bob@example.org>
+To: bob@example.org
+Cc: "Maxime Ripard" <mripard@kernel.org>
+Content-Type: text/plain; charset=utf-8
+Content-Transfer-Encoding: 7bit
+
+bob@example.org wrote:
+> It's been unused since the baz rework.
+> 
+> Signed-off-by: bob@example.org
+
+Tested-by: Maxime Ripard <mripard@kernel.org>
+
+Thanks!
+Maxime
+"#]
+    );
+
+    assert!(matches!(
+        send(&["-t", &sample("no-message-id.eml")]),
+        Err(Error::MissingHeader("Message-ID"))
+    ));
+
+    let parent = sample("no-message-id.eml");
+    assert!(matches!(
+        send(&["-t", "--parent", &parent, &sample("minimal.eml")]),
+        Err(Error::NoParentMessageId(p)) if p.display().to_string() == parent
+    ));
+}
+
+#[test]
+fn series_replies_keep_the_whole_thread() {
+    let replies = send(&["-r", &sample("series.mbx")]).unwrap();
+
+    assert_eq!(
+        replies,
+        [
+            r#"Date: ...
+Message-ID: ...
+User-Agent: ...
+From: "Maxime Ripard" <mripard@kernel.org>
+Subject: Re: [PATCH v3 1/2] arm64: dts: allwinner: h6: add the GPU node
+In-Reply-To: <v3-1@example.com>
+References: <v2-0@example.com> <v2-1@example.com> <v2-2@example.com>
+ <v2-3@example.com> <v2-4@example.com> <v2-5@example.com> <v3-0@example.com>
+ <v3-1@example.com>
+To: "Jane Doe" <jane@example.com>
+Cc: linux-arm-kernel@lists.infradead.org, "Chen-Yu Tsai" <wens@csie.org>, "Maxime
+ Ripard" <mripard@kernel.org>
+Content-Type: text/plain; charset=utf-8
+Content-Transfer-Encoding: 7bit
+
+On Wed, 14 Oct 2026 08:01:00 +0000, Jane Doe wrote:
+> The H6 has a Mali T720, add its node.
+> 
+> Signed-off-by: Jane Doe <jane@example.com>
+
+Reviewed-by: Maxime Ripard <mripard@kernel.org>
+
+Thanks!
+Maxime
+"#,
+            r#"Date: ...
+Message-ID: ...
+User-Agent: ...
+From: "Maxime Ripard" <mripard@kernel.org>
+Subject: Re: [PATCH v3 2/2] arm64: dts: allwinner: pine-h64: enable the GPU
+In-Reply-To: <v3-2@example.com>
+References: <v2-0@example.com> <v2-1@example.com> <v2-2@example.com>
+ <v2-3@example.com> <v2-4@example.com> <v2-5@example.com> <v3-0@example.com>
+ <v3-2@example.com>
+To: "Jane Doe" <jane@example.com>
+Cc: linux-arm-kernel@lists.infradead.org, "Chen-Yu Tsai" <wens@csie.org>, "Maxime
+ Ripard" <mripard@kernel.org>
+Content-Type: text/plain; charset=utf-8
+Content-Transfer-Encoding: 7bit
+
+On Wed, 14 Oct 2026 08:02:00 +0000, Jane Doe wrote:
+> Enable the GPU, and give it its regulator.
+> 
+> Signed-off-by: Jane Doe <jane@example.com>
+
+Reviewed-by: Maxime Ripard <mripard@kernel.org>
+
+Thanks!
+Maxime
+"#,
+        ]
+    );
+}